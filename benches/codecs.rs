@@ -0,0 +1,46 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use varint::codec::{Codec, Leb128, StreamVByte, VarIntG8iu, VarIntGb};
+
+const COUNT: usize = 100_000;
+
+/// Deterministic values with `bits` significant bits at most.
+fn values(bits: u32) -> Vec<u32> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..COUNT)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> (64 - bits)) as u32
+        })
+        .collect()
+}
+
+const INPUTS: [(&str, u32); 4] = [("7bit", 7), ("14bit", 14), ("21bit", 21), ("32bit", 32)];
+
+fn bench<C: Codec>(c: &mut Criterion) {
+    let mut group = c.benchmark_group(C::NAME);
+    group.throughput(Throughput::Elements(COUNT as u64));
+    for &(name, bits) in &INPUTS {
+        let values = values(bits);
+        let encoded = C::encode(&values);
+        let mut buf = vec![0; C::max_encoded_len(COUNT)];
+        let mut decoded = vec![0; COUNT];
+        group.bench_with_input(BenchmarkId::new("encode", name), &values, |b, v| {
+            b.iter(|| C::encode_into(black_box(v), &mut buf))
+        });
+        group.bench_with_input(BenchmarkId::new("decode", name), &encoded, |b, d| {
+            b.iter(|| C::decode_into(black_box(d), &mut decoded))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench::<Leb128>,
+    bench::<VarIntGb>,
+    bench::<VarIntG8iu>,
+    bench::<StreamVByte>
+);
+criterion_main!(benches);
@@ -0,0 +1,73 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+const COUNT: usize = 100_000;
+
+/// Deterministic values with `bits` significant bits at most, and a random number
+/// of them for `bits == 0`.
+fn values(bits: u32) -> Vec<u64> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..COUNT)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let bits = if bits == 0 {
+                state as u32 % 64 + 1
+            } else {
+                bits
+            };
+            state >> (64 - bits)
+        })
+        .collect()
+}
+
+const INPUTS: [(&str, u32); 4] = [("7bit", 7), ("14bit", 14), ("28bit", 28), ("mixed", 0)];
+
+fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    for &(name, bits) in &INPUTS {
+        let wide = values(bits);
+        let narrow: Vec<u32> = wide.iter().map(|&value| value as u32).collect();
+        group.bench_with_input(BenchmarkId::new("u32/scalar", name), &narrow, |b, v| {
+            b.iter(|| varint::encode_packed(black_box(v)))
+        });
+        group.bench_with_input(BenchmarkId::new("u32/simd", name), &narrow, |b, v| {
+            b.iter(|| varint::encode_packed_u32(black_box(v)))
+        });
+        group.bench_with_input(BenchmarkId::new("u64/scalar", name), &wide, |b, v| {
+            b.iter(|| varint::encode_packed(black_box(v)))
+        });
+        group.bench_with_input(BenchmarkId::new("u64/simd", name), &wide, |b, v| {
+            b.iter(|| varint::encode_packed_u64(black_box(v)))
+        });
+    }
+    group.finish();
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(COUNT as u64));
+    for &(name, bits) in &INPUTS {
+        let wide = values(bits);
+        let narrow: Vec<u32> = wide.iter().map(|&value| value as u32).collect();
+        let data = varint::encode_packed(&narrow);
+        group.bench_with_input(BenchmarkId::new("u32/scalar", name), &data, |b, d| {
+            b.iter(|| varint::decode_packed::<u32>(black_box(d)))
+        });
+        group.bench_with_input(BenchmarkId::new("u32/simd", name), &data, |b, d| {
+            b.iter(|| varint::decode_packed_u32(black_box(d)))
+        });
+        let data = varint::encode_packed(&wide);
+        group.bench_with_input(BenchmarkId::new("u64/scalar", name), &data, |b, d| {
+            b.iter(|| varint::decode_packed::<u64>(black_box(d)))
+        });
+        group.bench_with_input(BenchmarkId::new("u64/simd", name), &data, |b, d| {
+            b.iter(|| varint::decode_packed_u64(black_box(d)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, decode);
+criterion_main!(benches);
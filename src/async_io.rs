@@ -0,0 +1,378 @@
+//! State machines and futures shared by the `tokio` and `futures_io` modules.
+//!
+//! Both modules invoke `async_varint_ext!`, which generates the extension traits,
+//! their futures and tests. A module only provides `poll_read_byte`, the adapter
+//! to its `AsyncRead::poll_read`, and the `AsyncRead` and `AsyncBufRead` impls of
+//! the test reader `Trickle`.
+#[cfg(test)]
+use core::task::{Context, Poll};
+use std::io;
+
+use crate::{DecodeError, VarIntDecode, VarIntEncode, MAX_VARINT_LEN};
+
+/// Bytes of a varint read so far.
+pub(crate) struct ReadState {
+    buf: [u8; MAX_VARINT_LEN],
+    len: usize,
+}
+
+impl ReadState {
+    pub(crate) fn new() -> ReadState {
+        ReadState {
+            buf: [0; MAX_VARINT_LEN],
+            len: 0,
+        }
+    }
+
+    /// Returns whether no byte has been added yet.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a byte read from the stream, returning the decoded value once the
+    /// last byte of the varint has been added.
+    pub(crate) fn push<T: VarIntDecode>(&mut self, byte: u8) -> Option<io::Result<T>> {
+        self.buf[self.len] = byte;
+        self.len += 1;
+        if (byte & 0x80) != 0x80 {
+            Some(T::try_from_varint(&self.buf[..self.len]).map_err(io::Error::from))
+        } else if self.len == MAX_VARINT_LEN {
+            Some(Err(DecodeError::TooLong.into()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Encoded varint and the number of its bytes written so far.
+pub(crate) struct WriteState {
+    buf: [u8; MAX_VARINT_LEN],
+    len: usize,
+    pos: usize,
+}
+
+impl WriteState {
+    pub(crate) fn new<T: VarIntEncode>(value: T) -> WriteState {
+        let mut buf = [0; MAX_VARINT_LEN];
+        let len = value.encode_into(&mut buf);
+        WriteState { buf, len, pos: 0 }
+    }
+
+    /// Bytes still to be written.
+    pub(crate) fn remaining(&self) -> &[u8] {
+        &self.buf[self.pos..self.len]
+    }
+
+    /// Marks `n` more bytes as written, returning the length of the varint once all
+    /// bytes have been written.
+    pub(crate) fn advance(&mut self, n: usize) -> io::Result<Option<usize>> {
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        self.pos += n;
+        if self.pos == self.len {
+            Ok(Some(self.len))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Reader for tests that is pending before every read and returns at most `chunk`
+/// bytes per read.
+#[cfg(test)]
+pub(crate) struct Trickle<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    pending: bool,
+}
+
+#[cfg(test)]
+impl<'a> Trickle<'a> {
+    pub(crate) fn new(data: &'a [u8], chunk: usize) -> Trickle<'a> {
+        Trickle {
+            data,
+            chunk,
+            pending: false,
+        }
+    }
+
+    /// Returns the bytes still to be read.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns the next bytes to be read, every other call being pending.
+    pub(crate) fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<&'a [u8]> {
+        self.pending = !self.pending;
+        if self.pending {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(&self.data[..self.chunk.min(self.data.len())])
+    }
+
+    /// Marks `n` bytes as read.
+    pub(crate) fn consume(&mut self, n: usize) {
+        self.data = &self.data[n..];
+    }
+}
+
+/// Generates the extension traits and futures of an async runtime module.
+///
+/// The invoking module imports `AsyncRead`, `AsyncWrite` and `AsyncBufRead` and
+/// defines `poll_read_byte`, which reads at most one byte and returns the number of
+/// bytes read. The `core` and `std` items used by the futures are imported here.
+macro_rules! async_varint_ext {
+    ($runtime:literal) => {
+        use core::future::Future;
+        use core::marker::PhantomData;
+        use core::pin::Pin;
+        use core::task::{ready, Context, Poll};
+        use std::io;
+
+        use crate::async_io::{ReadState, WriteState};
+        use crate::{VarIntDecode, VarIntEncode};
+
+        #[doc = concat!("Extension trait to read varints from any ", $runtime, " `AsyncRead`.")]
+        pub trait AsyncVarIntReadExt: AsyncRead {
+            /// Reads exactly the bytes of one varint and decodes it into `T`.
+            ///
+            /// The bytes are read one at a time, so wrap unbuffered readers in a
+            /// `BufReader`. Fails with `UnexpectedEof` when the stream ends inside the
+            /// varint and with `InvalidData` when the value does not fit in `T`.
+            ///
+            /// This method is not cancellation safe: bytes already read are lost when
+            /// the future is dropped before it completes. Buffered readers can use
+            /// `AsyncVarIntBufReadExt::read_buffered_varint` instead.
+            fn read_varint<T: VarIntDecode>(&mut self) -> ReadVarInt<'_, Self, T>
+            where
+                Self: Unpin,
+            {
+                ReadVarInt {
+                    reader: self,
+                    state: ReadState::new(),
+                    marker: PhantomData,
+                }
+            }
+        }
+
+        impl<R: AsyncRead + ?Sized> AsyncVarIntReadExt for R {}
+
+        #[doc = concat!("Extension trait to read varints from any ", $runtime, " `AsyncBufRead`.")]
+        pub trait AsyncVarIntBufReadExt: AsyncBufRead {
+            /// Reads one varint from the buffer of the reader and decodes it into `T`.
+            ///
+            /// Fails like `AsyncVarIntReadExt::read_varint`.
+            ///
+            /// The varint is only consumed from the buffer once it has been decoded, so
+            /// the method is cancellation safe as long as the varint lies in the buffer
+            /// of the reader. When it spans the end of the buffer, the bytes before the
+            /// end have to be consumed to refill it and are lost if the future is
+            /// dropped before it completes.
+            fn read_buffered_varint<T: VarIntDecode>(&mut self) -> ReadBufferedVarInt<'_, Self, T>
+            where
+                Self: Unpin,
+            {
+                ReadBufferedVarInt {
+                    reader: self,
+                    state: ReadState::new(),
+                    marker: PhantomData,
+                }
+            }
+        }
+
+        impl<R: AsyncBufRead + ?Sized> AsyncVarIntBufReadExt for R {}
+
+        #[doc = concat!("Extension trait to write varints to any ", $runtime, " `AsyncWrite`.")]
+        pub trait AsyncVarIntWriteExt: AsyncWrite {
+            /// Writes `value` as a varint and returns the number of bytes written.
+            ///
+            /// This method is not cancellation safe: part of the varint may have been
+            /// written when the future is dropped before it completes.
+            fn write_varint<T: VarIntEncode>(&mut self, value: T) -> WriteVarInt<'_, Self>
+            where
+                Self: Unpin,
+            {
+                WriteVarInt {
+                    writer: self,
+                    state: WriteState::new(value),
+                }
+            }
+        }
+
+        impl<W: AsyncWrite + ?Sized> AsyncVarIntWriteExt for W {}
+
+        /// Future returned by `AsyncVarIntReadExt::read_varint`.
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        pub struct ReadVarInt<'a, R: ?Sized, T> {
+            reader: &'a mut R,
+            state: ReadState,
+            marker: PhantomData<fn() -> T>,
+        }
+
+        impl<R: AsyncRead + Unpin + ?Sized, T: VarIntDecode> Future for ReadVarInt<'_, R, T> {
+            type Output = io::Result<T>;
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<T>> {
+                let this = self.get_mut();
+                loop {
+                    let mut byte = [0u8; 1];
+                    if ready!(poll_read_byte(&mut *this.reader, cx, &mut byte))? == 0 {
+                        return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                    }
+                    if let Some(result) = this.state.push(byte[0]) {
+                        return Poll::Ready(result);
+                    }
+                }
+            }
+        }
+
+        /// Future returned by `AsyncVarIntBufReadExt::read_buffered_varint`.
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        pub struct ReadBufferedVarInt<'a, R: ?Sized, T> {
+            reader: &'a mut R,
+            state: ReadState,
+            marker: PhantomData<fn() -> T>,
+        }
+
+        impl<R: AsyncBufRead + Unpin + ?Sized, T: VarIntDecode> Future
+            for ReadBufferedVarInt<'_, R, T>
+        {
+            type Output = io::Result<T>;
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<T>> {
+                let this = self.get_mut();
+                loop {
+                    let buf = ready!(Pin::new(&mut *this.reader).poll_fill_buf(cx))?;
+                    if buf.is_empty() {
+                        return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                    }
+                    let (consumed, result) = match T::try_from_varint_with_len(buf) {
+                        Ok((value, len)) if this.state.is_empty() => (len, Some(Ok(value))),
+                        // the varint spans the end of the buffer or is malformed: consume
+                        // it byte by byte, failing like `read_varint`
+                        _ => {
+                            let mut consumed = 0;
+                            let mut result = None;
+                            for &byte in buf {
+                                consumed += 1;
+                                result = this.state.push(byte);
+                                if result.is_some() {
+                                    break;
+                                }
+                            }
+                            (consumed, result)
+                        }
+                    };
+                    Pin::new(&mut *this.reader).consume(consumed);
+                    if let Some(result) = result {
+                        return Poll::Ready(result);
+                    }
+                }
+            }
+        }
+
+        /// Future returned by `AsyncVarIntWriteExt::write_varint`.
+        #[must_use = "futures do nothing unless you `.await` or poll them"]
+        pub struct WriteVarInt<'a, W: ?Sized> {
+            writer: &'a mut W,
+            state: WriteState,
+        }
+
+        impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteVarInt<'_, W> {
+            type Output = io::Result<usize>;
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+                let this = self.get_mut();
+                loop {
+                    let n =
+                        ready!(Pin::new(&mut *this.writer).poll_write(cx, this.state.remaining()))?;
+                    if let Some(len) = this.state.advance(n)? {
+                        return Poll::Ready(Ok(len));
+                    }
+                }
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+            use crate::async_io::Trickle;
+            use futures::executor::block_on;
+            use std::vec::Vec;
+
+            #[test]
+            fn read_trickle() {
+                let mut reader = Trickle::new(&[254, 149, 3, 172], 1);
+                assert_eq!(block_on(reader.read_varint::<u16>()).unwrap(), 0xcafe);
+                let err = block_on(reader.read_varint::<u16>()).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            }
+
+            #[test]
+            fn read_overflow() {
+                let mut reader = &[0x80u8, 0x80, 0x04][..];
+                let err = block_on(reader.read_varint::<u16>()).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                let mut reader = &[0x80u8, 0x80, 0x04, 1][..];
+                let err = block_on(reader.read_buffered_varint::<u16>()).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+                assert_eq!(reader, [1]);
+            }
+
+            #[test]
+            fn read_buffered_trickle() {
+                // varints spanning the end of the buffer
+                let mut reader = Trickle::new(&[254, 149, 3, 1, 172], 2);
+                assert_eq!(
+                    block_on(reader.read_buffered_varint::<u16>()).unwrap(),
+                    0xcafe
+                );
+                assert_eq!(block_on(reader.read_buffered_varint::<u16>()).unwrap(), 1);
+                let err = block_on(reader.read_buffered_varint::<u16>()).unwrap_err();
+                assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+            }
+
+            #[test]
+            fn read_buffered_cancelled() {
+                let waker = futures::task::noop_waker();
+                let mut cx = Context::from_waker(&waker);
+                let mut reader = Trickle::new(&[254, 149, 3], 8);
+                {
+                    let mut future = reader.read_buffered_varint::<u16>();
+                    assert!(Pin::new(&mut future).poll(&mut cx).is_pending());
+                }
+                assert_eq!(reader.remaining(), [254, 149, 3]);
+                assert_eq!(
+                    block_on(reader.read_buffered_varint::<u16>()).unwrap(),
+                    0xcafe
+                );
+                assert!(reader.remaining().is_empty());
+            }
+
+            quickcheck! {
+                fn write_read_i64(values: Vec<i64>) -> bool {
+                    block_on(async {
+                        let mut buf = Vec::new();
+                        for value in &values {
+                            buf.write_varint(*value).await.unwrap();
+                        }
+                        let mut reader = &buf[..];
+                        let mut buffered = Trickle::new(&buf, 7);
+                        for value in &values {
+                            if reader.read_varint::<i64>().await.unwrap() != *value
+                                || buffered.read_buffered_varint::<i64>().await.unwrap() != *value
+                            {
+                                return false;
+                            }
+                        }
+                        reader.is_empty() && buffered.remaining().is_empty()
+                    })
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use async_varint_ext;
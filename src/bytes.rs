@@ -0,0 +1,107 @@
+//! Extension traits to read and write varints from `bytes` buffers.
+//!
+//! ```
+//!    use bytes::BytesMut;
+//!    use varint::bytes::{VarIntBuf, VarIntBufMut};
+//!
+//!    let mut buf = BytesMut::new();
+//!    buf.put_varint(300u32);
+//!    buf.put_varint(-300i32);
+//!
+//!    let mut buf = buf.freeze();
+//!    assert_eq!(Ok(300u32), buf.get_varint());
+//!    assert_eq!(Ok(-300i32), buf.get_varint());
+//! ```
+use ::bytes::{Buf, BufMut};
+
+use crate::{DecodeError, VarIntDecode, VarIntEncode, MAX_VARINT_LEN};
+
+/// Extension trait to read varints from any `bytes::Buf`.
+pub trait VarIntBuf: Buf {
+    /// Reads one varint from the buffer and decodes it into `T`, advancing the
+    /// buffer past the varint.
+    ///
+    /// Varints spanning several chunks of a non-contiguous buffer are supported.
+    /// When an error is returned the position of the buffer is unspecified.
+    fn get_varint<T: VarIntDecode>(&mut self) -> Result<T, DecodeError> {
+        let chunk = self.chunk();
+        match T::try_from_varint_with_len(chunk) {
+            Ok((value, len)) => {
+                self.advance(len);
+                return Ok(value);
+            }
+            // the varint continues in the next chunk
+            Err(DecodeError::Truncated) | Err(DecodeError::Empty)
+                if chunk.len() < self.remaining() => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        let mut len = 0;
+        while len < MAX_VARINT_LEN && self.has_remaining() {
+            buf[len] = self.get_u8();
+            len += 1;
+            if (buf[len - 1] & 0x80) != 0x80 {
+                break;
+            }
+        }
+        T::try_from_varint(&buf[..len])
+    }
+}
+
+impl<B: Buf + ?Sized> VarIntBuf for B {}
+
+/// Extension trait to write varints to any `bytes::BufMut`.
+pub trait VarIntBufMut: BufMut {
+    /// Writes `value` as a varint.
+    ///
+    /// Panics when the buffer does not have enough capacity, like the other
+    /// `put_*` methods of `BufMut`.
+    fn put_varint<T: VarIntEncode>(&mut self, value: T) {
+        let (array, len) = value.encode_to_array();
+        self.put_slice(&array.as_ref()[..len]);
+    }
+}
+
+impl<B: BufMut + ?Sized> VarIntBufMut for B {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn get_varint_chained() {
+        let mut buf = (&[254u8, 149][..]).chain(&[3u8, 172, 2][..]);
+        assert_eq!(buf.get_varint::<u16>(), Ok(0xcafe));
+        assert_eq!(buf.get_varint::<u32>(), Ok(300));
+        assert!(!buf.has_remaining());
+        assert_eq!(buf.get_varint::<u32>(), Err(DecodeError::Empty));
+    }
+
+    #[test]
+    fn get_varint_errors() {
+        let mut buf = (&[254u8][..]).chain(&[149u8][..]);
+        assert_eq!(buf.get_varint::<u16>(), Err(DecodeError::Truncated));
+        let mut buf = (&[254u8][..]).chain(&[149u8, 4][..]);
+        assert_eq!(
+            buf.get_varint::<u16>(),
+            Err(DecodeError::Overflow { target_bits: 16 })
+        );
+        let mut buf = &[0x80u8; 20][..];
+        assert_eq!(buf.get_varint::<u64>(), Err(DecodeError::TooLong));
+    }
+
+    quickcheck! {
+        fn put_get_i64(values: Vec<i64>, split: usize) -> bool {
+            let mut data = Vec::new();
+            for value in &values {
+                data.put_varint(*value);
+            }
+            let split = if data.is_empty() { 0 } else { split % data.len() };
+            let mut buf = (&data[..split]).chain(&data[split..]);
+            values.iter().all(|value| buf.get_varint::<i64>() == Ok(*value))
+                && !buf.has_remaining()
+        }
+    }
+}
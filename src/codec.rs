@@ -0,0 +1,237 @@
+//! A common interface to the byte aligned encodings of `u32` slices.
+//!
+//! Every encoding has a unit type implementing `Codec`, so code generic over the
+//! codec, such as a benchmark, can swap formats.
+//!
+//! The `stream_vbyte`, `group_varint` and `varint_g8iu` modules share the layout of
+//! this trait: none of the formats stores the number of values, which has to be
+//! stored alongside the encoded data and passed to `decode`. Encoding `count`
+//! values takes at most `max_encoded_len(count)` bytes, so a buffer of that size
+//! is always enough; `encode_into` panics when the encoded values do not fit.
+//!
+//! ```
+//!    use varint::codec::{Codec, Leb128, StreamVByte, VarIntG8iu, VarIntGb};
+//!
+//!    fn round_trip<C: Codec>(values: &[u32; 5]) -> [u32; 5] {
+//!        let mut buf = [0; 32];
+//!        let len = C::encode_into(values, &mut buf[..C::max_encoded_len(5)]);
+//!        let mut decoded = [0; 5];
+//!        assert_eq!(C::decode_into(&buf[..len], &mut decoded), Ok(len));
+//!        decoded
+//!    }
+//!
+//!    let values = [1, 300, 70000, 0, 1 << 31];
+//!    assert_eq!(round_trip::<Leb128>(&values), values);
+//!    assert_eq!(round_trip::<VarIntGb>(&values), values);
+//!    assert_eq!(round_trip::<VarIntG8iu>(&values), values);
+//!    assert_eq!(round_trip::<StreamVByte>(&values), values);
+//! ```
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{group_varint, stream_vbyte, varint_g8iu, DecodeError, VarIntIter};
+
+/// Encoding of `u32` slices into bytes.
+pub trait Codec {
+    /// Name of the format.
+    const NAME: &'static str;
+
+    /// Returns the maximum number of bytes needed to encode `count` values.
+    fn max_encoded_len(count: usize) -> usize;
+
+    /// Encodes `values` into the start of `buf` and returns the number of bytes
+    /// written.
+    ///
+    /// Panics when the encoded values do not fit in `buf`;
+    /// `max_encoded_len(values.len())` bytes are always enough.
+    fn encode_into(values: &[u32], buf: &mut [u8]) -> usize;
+
+    /// Decodes `values.len()` values from `data` and returns the number of bytes
+    /// read.
+    fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError>;
+
+    /// Encodes `values`.
+    #[cfg(feature = "alloc")]
+    fn encode(values: &[u32]) -> Vec<u8> {
+        encode_vec(Self::max_encoded_len(values.len()), |buf| {
+            Self::encode_into(values, buf)
+        })
+    }
+
+    /// Decodes `count` values from `data`.
+    #[cfg(feature = "alloc")]
+    fn decode(data: &[u8], count: usize) -> Result<Vec<u32>, DecodeError> {
+        decode_vec(count, |values| Self::decode_into(data, values))
+    }
+}
+
+/// Encodes with `encode_into` into a buffer of `max_len` bytes, truncated to the
+/// bytes written.
+#[cfg(feature = "alloc")]
+pub(crate) fn encode_vec(max_len: usize, encode_into: impl FnOnce(&mut [u8]) -> usize) -> Vec<u8> {
+    let mut output = alloc::vec![0; max_len];
+    let len = encode_into(&mut output);
+    output.truncate(len);
+    output
+}
+
+/// Decodes `count` values with `decode_into`.
+#[cfg(feature = "alloc")]
+pub(crate) fn decode_vec<T: Clone + Default>(
+    count: usize,
+    decode_into: impl FnOnce(&mut [T]) -> Result<usize, DecodeError>,
+) -> Result<Vec<T>, DecodeError> {
+    let mut values = alloc::vec![T::default(); count];
+    decode_into(&mut values)?;
+    Ok(values)
+}
+
+/// Consecutive LEB128 varints, as written by `encode_packed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Leb128;
+
+impl Codec for Leb128 {
+    const NAME: &'static str = "leb128";
+
+    fn max_encoded_len(count: usize) -> usize {
+        <u32 as crate::VarIntEncode>::MAX_ENCODED_LEN * count
+    }
+
+    fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+        crate::encode_packed_into(values, buf)
+    }
+
+    fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+        let mut iter = VarIntIter::new(data);
+        for value in values.iter_mut() {
+            *value = iter.next().ok_or(DecodeError::Truncated)??;
+        }
+        Ok(iter.offset())
+    }
+
+    #[cfg(feature = "alloc")]
+    fn encode(values: &[u32]) -> Vec<u8> {
+        crate::encode_packed_u32(values)
+    }
+}
+
+/// Varint-GB, the Group Varint format of the `group_varint` module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VarIntGb;
+
+impl Codec for VarIntGb {
+    const NAME: &'static str = "varint-gb";
+
+    fn max_encoded_len(count: usize) -> usize {
+        group_varint::max_encoded_len(count)
+    }
+
+    fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+        group_varint::encode_into(values, buf)
+    }
+
+    fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+        group_varint::decode_into(data, values)
+    }
+}
+
+/// Varint-G8IU, the format of the `varint_g8iu` module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VarIntG8iu;
+
+impl Codec for VarIntG8iu {
+    const NAME: &'static str = "varint-g8iu";
+
+    fn max_encoded_len(count: usize) -> usize {
+        varint_g8iu::max_encoded_len(count)
+    }
+
+    fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+        varint_g8iu::encode_into(values, buf)
+    }
+
+    fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+        varint_g8iu::decode_into(data, values)
+    }
+}
+
+/// Stream VByte, the format of the `stream_vbyte` module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StreamVByte;
+
+impl Codec for StreamVByte {
+    const NAME: &'static str = "stream-vbyte";
+
+    fn max_encoded_len(count: usize) -> usize {
+        stream_vbyte::max_encoded_len(count)
+    }
+
+    fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+        stream_vbyte::encode_into(values, buf)
+    }
+
+    fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+        stream_vbyte::decode_into(data, values)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::test_util::shifted;
+    use std::vec::Vec;
+
+    fn round_trip<C: Codec>(values: &[u32]) -> bool {
+        let encoded = C::encode(values);
+        let mut decoded = vec![0; values.len()];
+        encoded.len() <= C::max_encoded_len(values.len())
+            && C::decode_into(&encoded, &mut decoded) == Ok(encoded.len())
+            && decoded == values
+    }
+
+    /// Checks what every format does alike: the bound on the encoded length, empty
+    /// input, input ending inside the values and bytes after them.
+    fn edges<C: Codec>() {
+        assert_eq!(C::encode(&[u32::MAX; 5]).len(), C::max_encoded_len(5));
+        assert_eq!(C::encode(&[]), []);
+        assert_eq!(C::decode(&[], 0), Ok(vec![]));
+        assert_eq!(C::decode(&[], 1), Err(DecodeError::Truncated));
+
+        let values = [1, 300, 70000];
+        let mut encoded = C::encode(&values);
+        let len = encoded.len();
+        assert_eq!(
+            C::decode(&encoded[..len - 1], values.len()),
+            Err(DecodeError::Truncated)
+        );
+        encoded.push(42);
+        let mut decoded = [0; 3];
+        assert_eq!(C::decode_into(&encoded, &mut decoded), Ok(len));
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn codec_edges() {
+        edges::<Leb128>();
+        edges::<VarIntGb>();
+        edges::<VarIntG8iu>();
+        edges::<StreamVByte>();
+    }
+
+    #[test]
+    fn leb128_truncated() {
+        assert_eq!(Leb128::decode(&[1, 2], 3), Err(DecodeError::Truncated));
+        assert_eq!(Leb128::decode(&[1, 172], 2), Err(DecodeError::Truncated));
+        assert_eq!(Leb128::decode(&[1, 2, 3], 2), Ok(vec![1, 2]));
+    }
+
+    quickcheck! {
+        fn codecs_u32(values: Vec<u32>, shifts: Vec<u8>) -> bool {
+            let values = shifted(&values, &shifts);
+            round_trip::<Leb128>(&values)
+                && round_trip::<VarIntGb>(&values)
+                && round_trip::<VarIntG8iu>(&values)
+                && round_trip::<StreamVByte>(&values)
+        }
+    }
+}
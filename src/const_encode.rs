@@ -0,0 +1,127 @@
+//! Encoding at compile time, with `const fn` encoders per type and the `varint!` macro.
+use crate::{encode_array, VarIntEncode, MAX_VARINT_LEN};
+
+/// Defines a `const fn` encoding an unsigned type into an array of its maximum varint length.
+macro_rules! const_encode_unsigned {
+    ($name:ident, $t:ty) =>
+    (
+        #[doc = concat!("Encodes a `", stringify!($t), "` into a varint in a `const` context.")]
+        ///
+        /// Returns the bytes in an array as long as the longest varint of the type,
+        /// together with the number of bytes used.
+        pub const fn $name(value: $t) -> ([u8; <$t as VarIntEncode>::MAX_ENCODED_LEN], usize) {
+            encode_array(value as u128)
+        }
+    )
+}
+
+/// Defines a `const fn` ZigZag encoding a signed type with unsigned counterpart `$u`
+/// into an array of its maximum varint length.
+macro_rules! const_encode_signed {
+    ($name:ident, $t:ty, $u:ty) =>
+    (
+        #[doc = concat!("Encodes an `", stringify!($t), "` into a ZigZag varint in a `const` context.")]
+        ///
+        /// Returns the bytes in an array as long as the longest varint of the type,
+        /// together with the number of bytes used.
+        pub const fn $name(value: $t) -> ([u8; <$t as VarIntEncode>::MAX_ENCODED_LEN], usize) {
+            let value = ((value << 1) ^ (value >> (<$t>::BITS - 1))) as $u;
+            encode_array(value as u128)
+        }
+    )
+}
+
+const_encode_unsigned!(encode_u8, u8);
+const_encode_unsigned!(encode_u16, u16);
+const_encode_unsigned!(encode_u32, u32);
+const_encode_unsigned!(encode_u64, u64);
+const_encode_unsigned!(encode_u128, u128);
+const_encode_unsigned!(encode_usize, usize);
+const_encode_signed!(encode_i8, i8, u8);
+const_encode_signed!(encode_i16, i16, u16);
+const_encode_signed!(encode_i32, i32, u32);
+const_encode_signed!(encode_i64, i64, u64);
+const_encode_signed!(encode_i128, i128, u128);
+const_encode_signed!(encode_isize, isize, usize);
+
+/// Returns the first `N` bytes of an encoded varint, used by the `varint!` macro.
+#[doc(hidden)]
+pub const fn __prefix<const N: usize>(array: [u8; MAX_VARINT_LEN]) -> [u8; N] {
+    let mut prefix = [0; N];
+    let mut i = 0;
+    while i < N {
+        prefix[i] = array[i];
+        i += 1;
+    }
+    prefix
+}
+
+/// Encodes an integer constant into a `&'static [u8]` varint at compile time.
+///
+/// Produces the same bytes as `to_varint`: signed types are ZigZag encoded.
+///
+/// ```
+///    use varint::varint;
+///
+///    const HEADER: &[u8] = varint!(300u32);
+///    assert_eq!(HEADER, &[172, 2]);
+///    assert_eq!(varint!(-5i64), &[9]);
+/// ```
+#[macro_export]
+macro_rules! varint {
+    ($value:expr) => {{
+        const ENCODED: ([u8; 19], usize) = {
+            let value = $value;
+            #[allow(clippy::eq_op)]
+            let zero = value ^ value;
+            // the arithmetic shift keeps the sign bit only for signed types
+            if ((!zero >> 1) as i128) < 0 {
+                let value = value as i128;
+                $crate::encode_to_array(((value << 1) ^ (value >> 127)) as u128)
+            } else {
+                $crate::encode_to_array(value as u128)
+            }
+        };
+        const LEN: usize = ENCODED.1;
+        const BYTES: [u8; LEN] = $crate::__prefix(ENCODED.0);
+        const SLICE: &[u8] = &BYTES;
+        SLICE
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::VarIntEncode;
+
+    #[test]
+    fn const_encoders() {
+        const CAFE: ([u8; 3], usize) = crate::encode_u16(0xcafe);
+        assert_eq!(CAFE, 0xcafeu16.encode_to_array());
+        assert_eq!(crate::encode_i8(i8::MIN), i8::MIN.encode_to_array());
+        assert_eq!(crate::encode_i128(-1), (-1i128).encode_to_array());
+    }
+
+    #[test]
+    fn varint_macro() {
+        const HEADER: &[u8] = varint!(0xcafeu16);
+        assert_eq!(HEADER, &[254, 149, 3]);
+        assert_eq!(varint!(0u8), &[0]);
+        assert_eq!(varint!(-1i8), &[1]);
+        assert_eq!(varint!(300), &[216, 4]);
+        assert_eq!(varint!(u128::MAX).len(), 19);
+        assert_eq!(varint!(i128::MIN), varint!(u128::MAX));
+        assert_eq!(varint!(i64::MIN), &i64::MIN.encode_to_array().0[..]);
+    }
+
+    quickcheck! {
+        fn const_encode_i64(val: i64) -> bool {
+            crate::encode_i64(val) == val.encode_to_array()
+        }
+    }
+
+    quickcheck! {
+        fn const_encode_u32(val: u32) -> bool {
+            crate::encode_u32(val) == val.encode_to_array()
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Delta encoding of integer sequences as consecutive varints.
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{DecodeError, VarIntEncode, VarIntIter};
+
+/// Error returned when a sequence passed as sorted decreases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnsortedError {
+    /// Index of the first value smaller than the value before it.
+    pub index: usize,
+}
+
+impl fmt::Display for UnsortedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "value at index {} is smaller than the one before",
+            self.index
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnsortedError {}
+
+/// Encodes a non-decreasing sequence as the varints of the differences between
+/// consecutive values, the first value being encoded as is.
+///
+/// Fails when a value is smaller than the one before.
+///
+/// ```
+///    let ids = [1000, 1003, 1003, 1010];
+///    let encoded = varint::encode_sorted_deltas(&ids).unwrap();
+///    assert_eq!(encoded, [232, 7, 3, 0, 7]);
+///    assert_eq!(varint::decode_sorted_deltas(&encoded), Ok(ids.to_vec()));
+///
+///    assert_eq!(varint::encode_sorted_deltas(&[2, 1]).unwrap_err().index, 1);
+/// ```
+pub fn encode_sorted_deltas(values: &[u64]) -> Result<Vec<u8>, UnsortedError> {
+    let mut output = Vec::with_capacity(values.len());
+    let mut prev = 0;
+    for (index, &value) in values.iter().enumerate() {
+        let delta = value.checked_sub(prev).ok_or(UnsortedError { index })?;
+        let (array, len) = delta.encode_to_array();
+        output.extend_from_slice(&array[..len]);
+        prev = value;
+    }
+    Ok(output)
+}
+
+/// Decodes a sequence encoded with `encode_sorted_deltas`.
+///
+/// Fails with `Overflow` when the sum of the differences does not fit in a `u64`.
+pub fn decode_sorted_deltas(data: &[u8]) -> Result<Vec<u64>, DecodeError> {
+    let mut prev = 0u64;
+    VarIntIter::<u64>::new(data)
+        .map(|delta| {
+            prev = prev
+                .checked_add(delta?)
+                .ok_or(DecodeError::Overflow { target_bits: 64 })?;
+            Ok(prev)
+        })
+        .collect()
+}
+
+/// Encodes any sequence as the ZigZag encoded varints of the differences between
+/// consecutive values, the first value being relative to 0.
+///
+/// Differences are computed with wrapping arithmetic and encoded as `i64`, so
+/// small steps in either direction take few bytes.
+///
+/// ```
+///    let offsets = [1000, 990, 1005];
+///    let encoded = varint::encode_zigzag_deltas(&offsets);
+///    assert_eq!(encoded, [208, 15, 19, 30]);
+///    assert_eq!(varint::decode_zigzag_deltas(&encoded), Ok(offsets.to_vec()));
+/// ```
+pub fn encode_zigzag_deltas(values: &[u64]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(values.len());
+    let mut prev = 0u64;
+    for &value in values {
+        let (array, len) = (value.wrapping_sub(prev) as i64).encode_to_array();
+        output.extend_from_slice(&array[..len]);
+        prev = value;
+    }
+    output
+}
+
+/// Decodes a sequence encoded with `encode_zigzag_deltas`.
+pub fn decode_zigzag_deltas(data: &[u8]) -> Result<Vec<u64>, DecodeError> {
+    let mut prev = 0u64;
+    VarIntIter::<i64>::new(data)
+        .map(|delta| {
+            prev = prev.wrapping_add(delta? as u64);
+            Ok(prev)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    #[test]
+    fn unsorted() {
+        assert_eq!(encode_sorted_deltas(&[]), Ok(vec![]));
+        assert_eq!(
+            encode_sorted_deltas(&[1, 5, 5, 4, 3]),
+            Err(UnsortedError { index: 3 })
+        );
+        assert_eq!(
+            UnsortedError { index: 3 }.to_string(),
+            "value at index 3 is smaller than the one before"
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(decode_sorted_deltas(&[1, 172]), Err(DecodeError::Truncated));
+        let mut data = u64::MAX.to_varint();
+        data.push(1);
+        assert_eq!(
+            decode_sorted_deltas(&data),
+            Err(DecodeError::Overflow { target_bits: 64 })
+        );
+        assert_eq!(
+            decode_zigzag_deltas(&data),
+            Ok(vec![1 << 63, (1 << 63) - 1])
+        );
+    }
+
+    quickcheck! {
+        fn sorted_deltas(values: Vec<u64>) -> bool {
+            let mut values = values;
+            values.sort_unstable();
+            let encoded = encode_sorted_deltas(&values).unwrap();
+            decode_sorted_deltas(&encoded) == Ok(values)
+        }
+    }
+
+    quickcheck! {
+        fn zigzag_deltas(values: Vec<u64>) -> bool {
+            decode_zigzag_deltas(&encode_zigzag_deltas(&values)) == Ok(values)
+        }
+    }
+}
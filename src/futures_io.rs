@@ -0,0 +1,55 @@
+//! Extension traits to read and write varints from futures-io `AsyncRead`,
+//! `AsyncBufRead` and `AsyncWrite`.
+//!
+//! ```
+//!    use varint::futures_io::{AsyncVarIntBufReadExt, AsyncVarIntReadExt, AsyncVarIntWriteExt};
+//!
+//!    # futures::executor::block_on(async {
+//!    let mut buf = Vec::new();
+//!    buf.write_varint(300u32).await.unwrap();
+//!    buf.write_varint(-300i32).await.unwrap();
+//!
+//!    let mut reader = &buf[..];
+//!    assert_eq!(300u32, reader.read_varint().await.unwrap());
+//!    assert_eq!(-300i32, reader.read_buffered_varint().await.unwrap());
+//!    # });
+//! ```
+use ::futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+
+crate::async_io::async_varint_ext!("futures-io");
+
+/// Reads at most one byte into `byte` and returns the number of bytes read.
+fn poll_read_byte<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    byte: &mut [u8; 1],
+) -> Poll<io::Result<usize>> {
+    Pin::new(reader).poll_read(cx, byte)
+}
+
+#[cfg(test)]
+impl AsyncRead for crate::async_io::Trickle<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let chunk = ready!(this.poll_chunk(cx));
+        let n = chunk.len().min(buf.len());
+        buf[..n].copy_from_slice(&chunk[..n]);
+        this.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+#[cfg(test)]
+impl AsyncBufRead for crate::async_io::Trickle<'_> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_chunk(cx).map(Ok)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume(amt);
+    }
+}
@@ -0,0 +1,180 @@
+//! Group Varint encoding of `u32` slices.
+//!
+//! Values are encoded in groups of four sharing one tag byte. The tag holds the
+//! lengths of the four values as 2 bit codes, the first value in the least
+//! significant bits, the code being the length in bytes minus one. The values
+//! follow the tag, each in as many little endian bytes as its code says, at least
+//! one byte for 0.
+//!
+//! When the number of values is not a multiple of four, the last group only holds
+//! the remaining values; the unused codes of its tag are 0 and have no data bytes.
+//! Like the other formats of the `codec` module, the encoding does not store the
+//! number of values.
+//!
+//! ```
+//!    use varint::group_varint;
+//!
+//!    let values = [1, 256, 65536, 16777216, 5];
+//!    let mut buf = [0; group_varint::max_encoded_len(5)];
+//!    let len = group_varint::encode_into(&values, &mut buf);
+//!    assert_eq!(buf[..len], [0xe4, 1, 0, 1, 0, 0, 1, 0, 0, 0, 1, 0x00, 5]);
+//!
+//!    let mut decoded = [0; 5];
+//!    assert_eq!(group_varint::decode_into(&buf[..len], &mut decoded), Ok(len));
+//!    assert_eq!(decoded, values);
+//! ```
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use crate::DecodeError;
+
+/// Offsets and lengths of the values of a group with a given tag.
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Offset of every value from the first byte after the tag.
+    offsets: [u8; 4],
+    /// Mask keeping the bytes of every value from a 4 byte load.
+    masks: [u32; 4],
+    /// Number of data bytes of the group.
+    len: u8,
+}
+
+static TABLE: [Entry; 256] = {
+    let mut table = [Entry {
+        offsets: [0; 4],
+        masks: [0; 4],
+        len: 0,
+    }; 256];
+    let mut tag = 0;
+    while tag < 256 {
+        let mut offset = 0;
+        let mut k = 0;
+        while k < 4 {
+            let len = ((tag >> (2 * k)) & 3) + 1;
+            table[tag].offsets[k] = offset as u8;
+            table[tag].masks[k] = u32::MAX >> (32 - 8 * len);
+            offset += len;
+            k += 1;
+        }
+        table[tag].len = offset as u8;
+        tag += 1;
+    }
+    table
+};
+
+/// Returns the maximum number of bytes needed to encode `count` values.
+pub const fn max_encoded_len(count: usize) -> usize {
+    count.div_ceil(4) + 4 * count
+}
+
+/// Encodes `values` into the start of `buf` and returns the number of bytes
+/// written.
+///
+/// Panics when the encoded values do not fit in `buf`, like `Codec::encode_into`.
+pub fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+    let mut pos = 0;
+    for group in values.chunks(4) {
+        let tag_pos = pos;
+        let mut tag = 0;
+        pos += 1;
+        for (k, value) in group.iter().enumerate() {
+            let len = (4 - value.leading_zeros() as usize / 8).max(1);
+            buf[pos..pos + len].copy_from_slice(&value.to_le_bytes()[..len]);
+            tag |= ((len - 1) << (2 * k)) as u8;
+            pos += len;
+        }
+        buf[tag_pos] = tag;
+    }
+    pos
+}
+
+/// Encodes `values`.
+#[cfg(feature = "alloc")]
+pub fn encode(values: &[u32]) -> Vec<u8> {
+    crate::codec::encode_vec(max_encoded_len(values.len()), |buf| {
+        encode_into(values, buf)
+    })
+}
+
+/// Decodes `values.len()` values from `data` and returns the number of bytes read.
+///
+/// Groups followed by at least 16 bytes are decoded with one unaligned load per
+/// value, using a table of offsets indexed by the tag.
+pub fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+    let mut pos = 0;
+    for group in values.chunks_mut(4) {
+        let tag = *data.get(pos).ok_or(DecodeError::Truncated)?;
+        let entry = &TABLE[tag as usize];
+        pos += 1;
+        match data.get(pos..pos + 16) {
+            Some(bytes) if group.len() == 4 => {
+                for (k, value) in group.iter_mut().enumerate() {
+                    let offset = entry.offsets[k] as usize;
+                    let word = u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+                    *value = word & entry.masks[k];
+                }
+                pos += entry.len as usize;
+            }
+            _ => {
+                for (k, value) in group.iter_mut().enumerate() {
+                    let len = ((tag >> (2 * k)) & 3) as usize + 1;
+                    let bytes = data.get(pos..pos + len).ok_or(DecodeError::Truncated)?;
+                    let mut le = [0u8; 4];
+                    le[..len].copy_from_slice(bytes);
+                    *value = u32::from_le_bytes(le);
+                    pos += len;
+                }
+            }
+        }
+    }
+    Ok(pos)
+}
+
+/// Decodes `count` values from `data`.
+///
+/// Bytes after the encoded values are ignored.
+#[cfg(feature = "alloc")]
+pub fn decode(data: &[u8], count: usize) -> Result<Vec<u32>, DecodeError> {
+    crate::codec::decode_vec(count, |values| decode_into(data, values))
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::test_util::shifted;
+    use std::vec::Vec;
+
+    #[test]
+    fn layout() {
+        assert_eq!(encode(&[0]), [0, 0]);
+        assert_eq!(encode(&[u32::MAX, 0]), [3, 0xff, 0xff, 0xff, 0xff, 0]);
+        assert_eq!(encode(&[1, 2, 3, 4, 5]), [0, 1, 2, 3, 4, 0, 5]);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        // the tag of the second group is missing
+        assert_eq!(decode(&[0, 1, 2, 3, 4], 5), Err(DecodeError::Truncated));
+    }
+
+    quickcheck! {
+        fn group_varint_u32(values: Vec<u32>) -> bool {
+            let encoded = encode(&values);
+            encoded.len() <= max_encoded_len(values.len())
+                && decode(&encoded, values.len()) == Ok(values)
+        }
+    }
+
+    quickcheck! {
+        fn group_varint_shifted(values: Vec<u32>, shifts: Vec<u8>, padding: u8) -> bool {
+            let values = shifted(&values, &shifts);
+            let mut encoded = encode(&values);
+            let len = encoded.len();
+            // trailing bytes move more groups onto the table driven path
+            encoded.resize(len + padding as usize % 20, 0xff);
+            let mut decoded = vec![0; values.len()];
+            decode_into(&encoded, &mut decoded) == Ok(len) && decoded == values
+        }
+    }
+}
@@ -0,0 +1,317 @@
+//! Extension traits to read and write varints from `std::io` streams.
+//!
+//! ```
+//!    use varint::{VarIntReader, VarIntWriter};
+//!
+//!    let mut buf = Vec::new();
+//!    buf.write_varint(300u32).unwrap();
+//!    buf.write_varint(-300i32).unwrap();
+//!
+//!    let mut reader = &buf[..];
+//!    assert_eq!(300u32, reader.read_varint().unwrap());
+//!    assert_eq!(-300i32, reader.read_varint().unwrap());
+//! ```
+//!
+//! Frames prefixed with a varint length, as written by protobuf's `writeDelimitedTo`,
+//! are read and written with `DelimitedReader` and `DelimitedWriter`.
+//!
+//! ```
+//!    use varint::io::{DelimitedReader, DelimitedWriter};
+//!
+//!    let mut writer = DelimitedWriter::new(Vec::new());
+//!    writer.write_frame(b"hello").unwrap();
+//!    writer.write_frame(b"world").unwrap();
+//!
+//!    let buf = writer.into_inner();
+//!    let frames: Vec<Vec<u8>> = DelimitedReader::new(&buf[..])
+//!        .collect::<Result<_, _>>()
+//!        .unwrap();
+//!    assert_eq!(frames, [b"hello", b"world"]);
+//! ```
+use std::io::{self, Read, Write};
+use std::vec::Vec;
+
+use super::MAX_VARINT_LEN;
+use crate::{DecodeError, VarIntDecode, VarIntEncode};
+
+impl From<DecodeError> for io::Error {
+    fn from(err: DecodeError) -> io::Error {
+        let kind = match err {
+            DecodeError::Empty | DecodeError::Truncated => io::ErrorKind::UnexpectedEof,
+            DecodeError::Overflow { .. } | DecodeError::TooLong => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// Extension trait to read varints from any `std::io::Read`.
+pub trait VarIntReader: Read {
+    /// Reads exactly the bytes of one varint and decodes it into `T`.
+    ///
+    /// The bytes are read one at a time, so wrap unbuffered readers in a
+    /// `BufReader`. Fails with `UnexpectedEof` when the stream ends inside the
+    /// varint and with `InvalidData` when the value does not fit in `T`.
+    fn read_varint<T: VarIntDecode>(&mut self) -> io::Result<T> {
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        for i in 0..MAX_VARINT_LEN {
+            self.read_exact(&mut buf[i..i + 1])?;
+            if (buf[i] & 0x80) != 0x80 {
+                return Ok(T::try_from_varint(&buf[..i + 1])?);
+            }
+        }
+        Err(DecodeError::TooLong.into())
+    }
+}
+
+impl<R: Read + ?Sized> VarIntReader for R {}
+
+/// Extension trait to write varints to any `std::io::Write`.
+pub trait VarIntWriter: Write {
+    /// Writes `value` as a varint and returns the number of bytes written.
+    fn write_varint<T: VarIntEncode>(&mut self, value: T) -> io::Result<usize> {
+        let (array, len) = value.encode_to_array();
+        self.write_all(&array.as_ref()[..len])?;
+        Ok(len)
+    }
+}
+
+impl<W: Write + ?Sized> VarIntWriter for W {}
+
+/// Reads frames prefixed with their length as a varint.
+///
+/// As an iterator it yields one `Vec` per frame and ends when the stream ends
+/// between two frames. A stream ending inside a frame yields an `UnexpectedEof`
+/// error. Frames longer than the maximum frame length (8 MiB by default) yield an
+/// `InvalidData` error. The iterator ends after an error, as the position in the
+/// stream is no longer at the start of a frame.
+#[derive(Debug)]
+pub struct DelimitedReader<R> {
+    reader: R,
+    max_frame_length: usize,
+    failed: bool,
+}
+
+impl<R: Read> DelimitedReader<R> {
+    /// Creates a reader with the default maximum frame length of 8 MiB.
+    pub fn new(reader: R) -> DelimitedReader<R> {
+        DelimitedReader::with_max_frame_length(reader, 8 * 1024 * 1024)
+    }
+
+    /// Creates a reader that accepts frames of at most `max_frame_length` bytes.
+    pub fn with_max_frame_length(reader: R, max_frame_length: usize) -> DelimitedReader<R> {
+        DelimitedReader {
+            reader,
+            max_frame_length,
+            failed: false,
+        }
+    }
+
+    /// Returns the maximum frame length.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Sets the maximum frame length.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.max_frame_length = max_frame_length;
+    }
+
+    /// Reads the next frame into `buf`, replacing its contents, so one buffer can
+    /// be reused for all frames.
+    ///
+    /// Returns `false` when the stream ends between two frames.
+    pub fn read_frame(&mut self, buf: &mut Vec<u8>) -> io::Result<bool> {
+        buf.clear();
+        let mut first = [0u8; 1];
+        loop {
+            match self.reader.read(&mut first) {
+                Ok(0) => return Ok(false),
+                Ok(_) => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let len: u64 = (&first[..]).chain(self.reader.by_ref()).read_varint()?;
+        if len > self.max_frame_length as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame length exceeds maximum",
+            ));
+        }
+        self.reader.by_ref().take(len).read_to_end(buf)?;
+        if buf.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(true)
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Returns a mutable reference to the underlying reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Iterator for DelimitedReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        if self.failed {
+            return None;
+        }
+        let mut buf = Vec::new();
+        match self.read_frame(&mut buf) {
+            Ok(true) => Some(Ok(buf)),
+            Ok(false) => None,
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Writes frames prefixed with their length as a varint.
+#[derive(Debug)]
+pub struct DelimitedWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> DelimitedWriter<W> {
+    /// Creates a writer of frames to `writer`.
+    pub fn new(writer: W) -> DelimitedWriter<W> {
+        DelimitedWriter { writer }
+    }
+
+    /// Writes the length of `data` as a varint, followed by `data`.
+    pub fn write_frame(&mut self, data: &[u8]) -> io::Result<()> {
+        self.writer.write_varint(data.len() as u64)?;
+        self.writer.write_all(data)
+    }
+
+    /// Flushes the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns a mutable reference to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_truncated() {
+        let mut reader = &[254u8, 149][..];
+        let err = reader.read_varint::<u16>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn read_overflow() {
+        let mut reader = &[0x80u8, 0x80, 0x04][..];
+        let err = reader.read_varint::<u16>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_too_long() {
+        let mut reader = &[0x80u8; 32][..];
+        let err = reader.read_varint::<u64>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_leaves_following_bytes() {
+        let mut reader = &[254u8, 149, 3, 42][..];
+        assert_eq!(reader.read_varint::<u16>().unwrap(), 0xcafe);
+        assert_eq!(reader, [42]);
+    }
+
+    quickcheck! {
+        fn write_read_i64(values: Vec<i64>) -> bool {
+            let mut buf = Vec::new();
+            for value in &values {
+                if buf.write_varint(*value).unwrap() != value.to_varint().len() {
+                    return false;
+                }
+            }
+            let mut reader = &buf[..];
+            values.iter().all(|value| reader.read_varint::<i64>().unwrap() == *value)
+                && reader.is_empty()
+        }
+    }
+
+    #[test]
+    fn delimited_eof() {
+        let mut reader = DelimitedReader::new(&[2u8, 1, 2, 0][..]);
+        let mut buf = vec![42];
+        assert!(reader.read_frame(&mut buf).unwrap());
+        assert_eq!(buf, [1, 2]);
+        assert!(reader.read_frame(&mut buf).unwrap());
+        assert!(buf.is_empty());
+        assert!(!reader.read_frame(&mut buf).unwrap());
+    }
+
+    #[test]
+    fn delimited_eof_inside_frame() {
+        let mut reader = DelimitedReader::new(&[3u8, 1, 2][..]);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(reader.next().is_none());
+        let mut reader = DelimitedReader::new(&[0xacu8][..]);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn delimited_too_long() {
+        let mut reader = DelimitedReader::with_max_frame_length(&[0xacu8, 2][..], 299);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // the body of the rejected frame is not read as the next frame
+        let data = [0xacu8, 2, 5, b'a', b'b', b'c', b'd', b'e'];
+        let mut reader = DelimitedReader::with_max_frame_length(&data[..], 10);
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(reader.next().is_none());
+    }
+
+    quickcheck! {
+        fn delimited_write_read(frames: Vec<Vec<u8>>) -> bool {
+            let mut writer = DelimitedWriter::new(Vec::new());
+            for frame in &frames {
+                writer.write_frame(frame).unwrap();
+            }
+            let buf = writer.into_inner();
+            let read: Vec<Vec<u8>> = DelimitedReader::new(&buf[..])
+                .collect::<io::Result<_>>()
+                .unwrap();
+            read == frames
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Iteration over a byte array of consecutive varints.
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+use crate::{DecodeError, VarIntDecode};
+
+/// Iterator decoding the consecutive varints in a byte array.
+///
+/// Yields one `Result` per varint. After an error, such as a truncated varint at
+/// the end of the data, the iterator ends and `remaining` and `offset` point at
+/// the start of the varint that could not be decoded.
+///
+/// ```
+///    use varint::{DecodeError, VarIntIter};
+///
+///    let mut iter = VarIntIter::<u32>::new(&[172, 2, 1, 172]);
+///    assert_eq!(iter.next(), Some(Ok(300)));
+///    assert_eq!(iter.next(), Some(Ok(1)));
+///    assert_eq!(iter.offset(), 3);
+///    assert_eq!(iter.next(), Some(Err(DecodeError::Truncated)));
+///    assert_eq!(iter.next(), None);
+///    assert_eq!(iter.remaining(), &[172]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VarIntIter<'a, T> {
+    data: &'a [u8],
+    offset: usize,
+    failed: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> VarIntIter<'a, T> {
+    /// Creates an iterator over the varints in `data`, starting at its first byte.
+    pub fn new(data: &'a [u8]) -> VarIntIter<'a, T> {
+        VarIntIter {
+            data,
+            offset: 0,
+            failed: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the bytes that have not been decoded yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.offset..]
+    }
+
+    /// Returns the offset of the next varint from the start of the data.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<T: VarIntDecode> Iterator for VarIntIter<'_, T> {
+    type Item = Result<T, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<T, DecodeError>> {
+        if self.failed || self.offset == self.data.len() {
+            return None;
+        }
+        match T::try_from_varint_with_len(self.remaining()) {
+            Ok((value, len)) => {
+                self.offset += len;
+                Some(Ok(value))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.failed {
+            (0, Some(0))
+        } else {
+            // any remaining byte yields a value or an error, which may end the iteration
+            let len = self.data.len() - self.offset;
+            (len.min(1), Some(len))
+        }
+    }
+}
+
+impl<T: VarIntDecode> FusedIterator for VarIntIter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode_packed_into;
+    use std::vec::Vec;
+
+    #[test]
+    fn iter_errors() {
+        let mut iter = VarIntIter::<u8>::new(&[1, 172, 2, 1]);
+        assert_eq!(iter.next(), Some(Ok(1)));
+        assert_eq!(
+            iter.next(),
+            Some(Err(DecodeError::Overflow { target_bits: 8 }))
+        );
+        assert_eq!(iter.offset(), 1);
+        assert_eq!(iter.next(), None);
+        assert_eq!(VarIntIter::<u8>::new(&[]).next(), None);
+    }
+
+    #[test]
+    fn size_hint_errors() {
+        let inputs: [&[u8]; 4] = [
+            &[],
+            &[0x80; 100],
+            &[1, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            &[1, 2, 0x80],
+        ];
+        for data in inputs {
+            let iter = VarIntIter::<u32>::new(data);
+            let (lower, upper) = iter.size_hint();
+            let count = iter.count();
+            assert!(lower <= count && upper.is_some_and(|upper| count <= upper));
+        }
+    }
+
+    quickcheck! {
+        fn iter_i32(values: Vec<i32>) -> bool {
+            let mut buf = vec![0; values.len() * 5];
+            let len = encode_packed_into(&values, &mut buf);
+            let mut iter = VarIntIter::<i32>::new(&buf[..len]);
+            let decoded: Vec<i32> = iter.by_ref().map(Result::unwrap).collect();
+            decoded == values && iter.offset() == len && iter.remaining().is_empty()
+        }
+    }
+}
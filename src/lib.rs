@@ -4,16 +4,22 @@
 //! Signed
 //!
 //! ```
+//! # #[cfg(feature = "alloc")]
+//! # {
 //!    use varint::VarIntEncode;
 //!    assert_eq!((-300i32).to_varint(), vec![215, 4]);
+//! # }
 //!
 //! ```
 //!
 //! Unsigned
 //!
 //! ```
+//! # #[cfg(feature = "alloc")]
+//! # {
 //!    use varint::VarIntEncode;
 //!    assert_eq!(300u32.to_varint(), vec![172, 2]);
+//! # }
 //!
 //! ```
 //!
@@ -35,87 +41,451 @@
 //!
 //! ```
 //!
+//! Fallible
+//!
+//! ```
+//!    use varint::{DecodeError, VarIntDecode};
+//!    assert_eq!(Ok(300u32), u32::try_from_varint(&[172, 2]));
+//!    assert_eq!(Err(DecodeError::Truncated), u32::try_from_varint(&[172]));
+//!
+//! ```
+//!
+//! ## Compile time encoding
+//!
+//! ```
+//!    const HEADER: &[u8] = varint::varint!(300u32);
+//!    assert_eq!(HEADER, &[172, 2]);
+//!
+//! ```
+//!
+//! ## Features
+//!
+//! The `std` feature (enabled by default) adds the `io` module and implements
+//! `std::error::Error` for `DecodeError`. Without it the crate is `no_std`; the
+//! `alloc` feature (implied by `std`) adds the `Vec` returning `to_varint` and `encode`,
+//! while the slice based `encode_into`, `encode_to_array` and decoding functions
+//! only need `core`. The `serde`, `bytes`, `tokio`, `futures-io` and `tokio-util`
+//! features add the modules of the same name.
+//!
+//! ## Other formats
+//!
+//! The `stream_vbyte`, `group_varint` and `varint_g8iu` modules encode `u32` slices
+//! in the Stream VByte, Group Varint (Varint-GB) and Varint-G8IU formats. The
+//! `codec` module puts them and LEB128 behind a common `Codec` trait.
+//!
+//! With the `alloc` feature, `encode_sorted_deltas` and `encode_zigzag_deltas`
+//! encode the differences between consecutive values, and the `timeseries` module
+//! encodes timestamps as delta-of-deltas. The `rle` module run-length encodes
+//! sequences with repeated values and decodes them lazily.
+#![no_std]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+#[macro_use]
+extern crate std;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+
+#[cfg(any(feature = "tokio", feature = "futures-io"))]
+mod async_io;
+#[cfg(feature = "bytes")]
+pub mod bytes;
+pub mod codec;
+#[cfg(feature = "futures-io")]
+pub mod futures_io;
+pub mod group_varint;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod rle;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod stream_vbyte;
+#[cfg(feature = "alloc")]
+pub mod timeseries;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+#[cfg(feature = "tokio-util")]
+pub mod tokio_util;
+pub mod varint_g8iu;
+
+mod const_encode;
+#[cfg(feature = "alloc")]
+mod delta;
+mod iter;
+mod packed;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod simd;
+#[cfg(all(test, feature = "alloc"))]
+mod test_util;
+
+#[cfg(feature = "std")]
+pub use io::{VarIntReader, VarIntWriter};
+#[doc(hidden)]
+pub use const_encode::__prefix;
+pub use const_encode::{encode_i128, encode_i16, encode_i32, encode_i64, encode_i8, encode_isize};
+pub use const_encode::{encode_u128, encode_u16, encode_u32, encode_u64, encode_u8, encode_usize};
+#[cfg(feature = "alloc")]
+pub use delta::{decode_sorted_deltas, decode_zigzag_deltas, encode_sorted_deltas};
+#[cfg(feature = "alloc")]
+pub use delta::{encode_zigzag_deltas, UnsortedError};
+pub use iter::VarIntIter;
+#[cfg(feature = "alloc")]
+pub use packed::{decode_packed, decode_packed_u32, decode_packed_u64};
+#[cfg(feature = "alloc")]
+pub use packed::{encode_packed, encode_packed_u32, encode_packed_u64};
+pub use packed::{encode_packed_into, packed_len};
+
+/// Maximum number of bytes in a varint holding a 128 bit integer.
+const MAX_VARINT_LEN: usize = 19;
+
 /// Trait to encode the type into a VarInt.
 ///
 /// ZigZag encoding is used for signed integers to reduce the number of bytes in the varint
 /// (without it, 10 bytes would be needed in the varint for all negative values).
 pub trait VarIntEncode {
-    fn to_varint(&self) -> Vec<u8>;
+    /// Number of bytes in the longest varint of the type.
+    const MAX_ENCODED_LEN: usize;
+
+    /// Byte array large enough to hold the varint of any value of the type.
+    type Array: AsRef<[u8]> + AsMut<[u8]> + Default;
+
+    #[cfg(feature = "alloc")]
+    fn to_varint(&self) -> Vec<u8> {
+        let (array, len) = self.encode_to_array();
+        array.as_ref()[..len].to_vec()
+    }
+
+    /// Encodes the value into the start of `buf` and returns the number of bytes written.
+    ///
+    /// Panics when `buf` is too small to hold the varint.
+    fn encode_into(&self, buf: &mut [u8]) -> usize;
+
+    /// Returns the number of bytes in the varint of the value, without encoding it.
+    fn encoded_len(&self) -> usize;
+
+    /// Encodes the value into a fixed size array, returned together with the
+    /// number of bytes used.
+    fn encode_to_array(&self) -> (Self::Array, usize) {
+        let mut array = Self::Array::default();
+        let len = self.encode_into(array.as_mut());
+        (array, len)
+    }
 }
 
 /// Trait to decode a byte array into the type.
 ///
-/// Warning: overflow of the target type is not detected!
-pub trait VarIntDecode {
-    fn from_varint(data: &[u8]) -> Self;
+/// Warning: overflow of the target type is not detected by `from_varint`!
+/// Use `try_from_varint` to reject values that do not fit.
+pub trait VarIntDecode: Sized {
+    fn from_varint(data: &[u8]) -> Self {
+        Self::from_varint_with_len(data).0
+    }
+
+    /// Decodes the varint at the start of a byte array into the type and also
+    /// returns the number of bytes it occupied, like `decode_with_len`. An empty
+    /// array decodes to 0 with a length of 0.
+    fn from_varint_with_len(data: &[u8]) -> (Self, usize);
+
+    /// Decodes a byte array into the type, rejecting malformed varints and
+    /// values that do not fit in the type.
+    fn try_from_varint(data: &[u8]) -> Result<Self, DecodeError> {
+        Self::try_from_varint_with_len(data).map(|(value, _)| value)
+    }
+
+    /// Decodes the varint at the start of a byte array into the type and also
+    /// returns the number of bytes it occupied, rejecting malformed varints.
+    fn try_from_varint_with_len(data: &[u8]) -> Result<(Self, usize), DecodeError>;
+}
+
+/// ZigZag encoding between a signed integer and the unsigned integer of the same width.
+///
+/// Small magnitudes map to small values (0, -1, 1, -2, ... become 0, 1, 2, 3, ...),
+/// which is how protobuf `sint32`/`sint64` fields are encoded.
+pub trait ZigZag: Sized {
+    type Unsigned;
+
+    fn zigzag_encode(self) -> Self::Unsigned;
+
+    fn zigzag_decode(value: Self::Unsigned) -> Self;
+}
+
+/// Wrapper to encode signed integers as sign-extended varints instead of ZigZag.
+///
+/// This is how protobuf `int32`/`int64` fields are encoded: negative values up to
+/// 64 bits wide take 10 bytes, `i128` values are sign-extended to 128 bits.
+///
+/// ```
+///    use varint::{SignExtended, VarIntDecode, VarIntEncode};
+///    let (array, len) = SignExtended(-1i32).encode_to_array();
+///    assert_eq!(&array[..len], &[255, 255, 255, 255, 255, 255, 255, 255, 255, 1]);
+///    assert_eq!(Ok(SignExtended(-1i32)), SignExtended::try_from_varint(&array[..len]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SignExtended<T>(pub T);
+
+/// Error returned when a byte array does not hold a valid varint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input contains no bytes.
+    Empty,
+    /// The input ends while the last byte still has the continuation bit set.
+    Truncated,
+    /// The decoded value does not fit in an integer of `target_bits` bits.
+    Overflow { target_bits: u32 },
+    /// The varint is longer than the longest valid encoding of a 128 bit integer.
+    TooLong,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Empty => write!(f, "empty varint"),
+            DecodeError::Truncated => write!(f, "truncated varint"),
+            DecodeError::Overflow { target_bits } => {
+                write!(f, "varint overflows a {} bit integer", target_bits)
+            }
+            DecodeError::TooLong => write!(f, "varint exceeds {} bytes", MAX_VARINT_LEN),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
 macro_rules! impl_varint_unsigned {
     ($t:ty) =>
     (
         impl VarIntEncode for $t {
-            fn to_varint(&self) -> Vec<u8> {
-                encode(*self as u128)
+            const MAX_ENCODED_LEN: usize = (<$t>::BITS as usize).div_ceil(7);
+            type Array = [u8; (<$t>::BITS as usize).div_ceil(7)];
+
+            fn encode_into(&self, buf: &mut [u8]) -> usize {
+                encode_into(*self as u128, buf)
+            }
+            fn encoded_len(&self) -> usize {
+                encoded_len(*self as u128)
             }
         }
         impl VarIntDecode for $t {
-            fn from_varint(data: &[u8]) -> Self {
-                decode(data) as Self
+            fn from_varint_with_len(data: &[u8]) -> (Self, usize) {
+                let (value, len) = decode_with_len(data);
+                (value as Self, len)
+            }
+            fn try_from_varint_with_len(data: &[u8]) -> Result<(Self, usize), DecodeError> {
+                let (value, len) = try_decode_with_len(data)?;
+                check_width(value, <$t>::BITS)?;
+                Ok((value as Self, len))
             }
         }
     )
 }
 
+/// Implements the traits for a signed type, using ZigZag encoding in the width of
+/// the type and its unsigned counterpart `$u`.
 macro_rules! impl_varint_signed {
-    ($t:ty) =>
+    ($t:ty, $u:ty) =>
     (
+        impl ZigZag for $t {
+            type Unsigned = $u;
+
+            fn zigzag_encode(self) -> $u {
+                ((self << 1) ^ (self >> (<$t>::BITS - 1))) as $u
+            }
+            fn zigzag_decode(value: $u) -> Self {
+                ((value >> 1) as $t) ^ (-((value & 1) as $t))
+            }
+        }
         impl VarIntEncode for $t {
-            fn to_varint(&self) -> Vec<u8> {
-                let value = *self as i128;
-                let value = (value << 1) ^ (value >> 63);
-                encode(value as u128)
+            const MAX_ENCODED_LEN: usize = (<$t>::BITS as usize).div_ceil(7);
+            type Array = [u8; (<$t>::BITS as usize).div_ceil(7)];
+
+            fn encode_into(&self, buf: &mut [u8]) -> usize {
+                encode_into(self.zigzag_encode() as u128, buf)
+            }
+            fn encoded_len(&self) -> usize {
+                encoded_len(self.zigzag_encode() as u128)
             }
         }
         impl VarIntDecode for $t {
-            fn from_varint(data: &[u8]) -> Self {
-                let value = decode(data) as i128;
-                ((value >> 1) ^ (-(value & 1))) as Self
+            fn from_varint_with_len(data: &[u8]) -> (Self, usize) {
+                let (value, len) = decode_with_len(data);
+                (<$t>::zigzag_decode(value as $u), len)
+            }
+            fn try_from_varint_with_len(data: &[u8]) -> Result<(Self, usize), DecodeError> {
+                let (value, len) = try_decode_with_len(data)?;
+                // the zigzag encoded value uses exactly as many bits as the signed type
+                check_width(value, <$t>::BITS)?;
+                Ok((<$t>::zigzag_decode(value as $u), len))
+            }
+        }
+    )
+}
+
+/// Implements the traits for `SignExtended<$t>`, sign-extending the value to `$s`
+/// and encoding it as its unsigned counterpart `$u`.
+macro_rules! impl_varint_sign_extended {
+    ($t:ty, $s:ty, $u:ty) =>
+    (
+        impl VarIntEncode for SignExtended<$t> {
+            const MAX_ENCODED_LEN: usize = (<$u>::BITS as usize).div_ceil(7);
+            type Array = [u8; (<$u>::BITS as usize).div_ceil(7)];
+
+            fn encode_into(&self, buf: &mut [u8]) -> usize {
+                encode_into(self.0 as $s as $u as u128, buf)
+            }
+            fn encoded_len(&self) -> usize {
+                encoded_len(self.0 as $s as $u as u128)
+            }
+        }
+        impl VarIntDecode for SignExtended<$t> {
+            fn from_varint_with_len(data: &[u8]) -> (Self, usize) {
+                let (value, len) = decode_with_len(data);
+                (SignExtended(value as $t), len)
+            }
+            fn try_from_varint_with_len(data: &[u8]) -> Result<(Self, usize), DecodeError> {
+                let (value, len) = try_decode_with_len(data)?;
+                check_width(value, <$u>::BITS)?;
+                let value = <$t>::try_from(value as $u as $s)
+                    .map_err(|_| DecodeError::Overflow { target_bits: <$t>::BITS })?;
+                Ok((SignExtended(value), len))
             }
         }
     )
 }
 
-/// Decodes an unsigned 64bit integer into a varint.
+/// Checks that a decoded value fits in an unsigned integer of `bits` bits.
+fn check_width(value: u128, bits: u32) -> Result<(), DecodeError> {
+    if bits < 128 && (value >> bits) != 0 {
+        Err(DecodeError::Overflow { target_bits: bits })
+    } else {
+        Ok(())
+    }
+}
+
+/// Encodes an unsigned 128bit integer into a varint.
+#[cfg(feature = "alloc")]
 pub fn encode(value: u128) -> Vec<u8> {
+    let (array, len) = encode_to_array(value);
+    array[..len].to_vec()
+}
+
+/// Encodes an unsigned 128bit integer into the start of `buf` and returns the number
+/// of bytes written.
+///
+/// Panics when `buf` is too small to hold the varint.
+pub const fn encode_into(value: u128, buf: &mut [u8]) -> usize {
     let mut value = value;
-    let mut output = Vec::<u8>::with_capacity(8);
+    let mut i = 0;
     while value > 127 {
-        output.push(((value as u8) & 127) | 0x80);
+        buf[i] = ((value as u8) & 127) | 0x80;
         value >>= 7;
+        i += 1;
     }
-    output.push((value as u8) & 127);
-    output
+    buf[i] = (value as u8) & 127;
+    i + 1
+}
+
+/// Returns the number of bytes in the varint of an unsigned 128bit integer.
+///
+/// ```
+///    const LEN: usize = varint::encoded_len(300);
+///    assert_eq!(LEN, 2);
+/// ```
+pub const fn encoded_len(value: u128) -> usize {
+    // zero still takes one byte
+    ((128 - (value | 1).leading_zeros()) as usize).div_ceil(7)
+}
+
+/// Encodes an unsigned 128bit integer into a fixed size array, returned together with
+/// the number of bytes used.
+pub const fn encode_to_array(value: u128) -> ([u8; MAX_VARINT_LEN], usize) {
+    encode_array(value)
+}
+
+/// Encodes an unsigned 128bit integer into an array of `N` bytes.
+const fn encode_array<const N: usize>(value: u128) -> ([u8; N], usize) {
+    let mut array = [0; N];
+    let len = encode_into(value, &mut array);
+    (array, len)
 }
 
-/// Decodes a byte array into an unsigned 64bit integer.
+/// Decodes a byte array into an unsigned 128bit integer.
 pub fn decode(data: &[u8]) -> u128 {
+    decode_with_len(data).0
+}
+
+/// Decodes a byte array into an unsigned 128bit integer and returns it together with
+/// the number of bytes read, so consecutive varints can be decoded from one buffer.
+///
+/// At most 19 bytes, the length of the longest 128bit varint, are read, even when
+/// they all have the continuation bit set. An empty array decodes to `(0, 0)`, so
+/// a loop over a buffer must stop at its end; `try_decode_with_len` reports both
+/// cases as errors.
+pub fn decode_with_len(data: &[u8]) -> (u128, usize) {
+    let data = &data[..data.len().min(MAX_VARINT_LEN)];
     let mut output: u128 = 0;
     for (i, b) in data.iter().enumerate() {
         output |= ((b & 127) as u128) << (7 * i);
         if (b & 0x80) != 0x80 {
             // stop when Most Significant Bit not set (last byte)
-            break;
+            return (output, i + 1);
+        }
+    }
+    (output, data.len())
+}
+
+/// Decodes a byte array into an unsigned 128bit integer, rejecting malformed varints.
+pub fn try_decode(data: &[u8]) -> Result<u128, DecodeError> {
+    try_decode_with_len(data).map(|(value, _)| value)
+}
+
+/// Decodes a byte array into an unsigned 128bit integer and returns it together with
+/// the number of bytes read, rejecting malformed varints.
+pub fn try_decode_with_len(data: &[u8]) -> Result<(u128, usize), DecodeError> {
+    if data.is_empty() {
+        return Err(DecodeError::Empty);
+    }
+    let mut output: u128 = 0;
+    for (i, b) in data.iter().enumerate() {
+        if i == MAX_VARINT_LEN - 1 {
+            if (b & 0x80) == 0x80 {
+                return Err(DecodeError::TooLong);
+            }
+            // only the lowest 2 bits of the last byte fit in 128 bits
+            if (b & 127) > 3 {
+                return Err(DecodeError::Overflow { target_bits: 128 });
+            }
+        }
+        output |= ((b & 127) as u128) << (7 * i);
+        if (b & 0x80) != 0x80 {
+            return Ok((output, i + 1));
         }
     }
-    output
+    Err(DecodeError::Truncated)
 }
 
+impl_varint_unsigned!(u8);
 impl_varint_unsigned!(u16);
 impl_varint_unsigned!(u32);
 impl_varint_unsigned!(u64);
-impl_varint_signed!(i16);
-impl_varint_signed!(i32);
-impl_varint_signed!(i64);
+impl_varint_unsigned!(u128);
+impl_varint_unsigned!(usize);
+impl_varint_signed!(i8, u8);
+impl_varint_signed!(i16, u16);
+impl_varint_signed!(i32, u32);
+impl_varint_signed!(i64, u64);
+impl_varint_signed!(i128, u128);
+impl_varint_signed!(isize, usize);
+impl_varint_sign_extended!(i8, i64, u64);
+impl_varint_sign_extended!(i16, i64, u64);
+impl_varint_sign_extended!(i32, i64, u64);
+impl_varint_sign_extended!(i64, i64, u64);
+impl_varint_sign_extended!(i128, i128, u128);
+impl_varint_sign_extended!(isize, i64, u64);
 
 #[cfg(test)]
 #[macro_use]
@@ -125,43 +495,411 @@ extern crate quickcheck;
 mod tests {
     use super::*;
 
+    fn varint(val: u128) -> [u8; MAX_VARINT_LEN] {
+        encode_to_array(val).0
+    }
+
     #[test]
+    #[cfg(feature = "alloc")]
     fn cafe_encode() {
         assert_eq!(0xcafeu16.to_varint(), vec![254, 149, 3]);
     }
 
+    #[test]
+    fn zigzag_extremes() {
+        assert_eq!((-1i8).encode_to_array(), ([1, 0], 1));
+        assert_eq!(i8::MIN.encode_to_array(), ([255, 1], 2));
+        assert_eq!(i8::try_from_varint(&[255, 1]), Ok(i8::MIN));
+        assert_eq!(i128::MAX.encode_to_array().0, varint(u128::MAX - 1));
+        assert_eq!(i128::MIN.encode_to_array().0, varint(u128::MAX));
+        assert_eq!(i128::try_from_varint(&varint(u128::MAX)), Ok(i128::MIN));
+        assert_eq!(i128::try_from_varint(&varint(u128::MAX - 1)), Ok(i128::MAX));
+    }
+
+    #[test]
+    fn cafe_encode_into() {
+        let mut buf = [0; 4];
+        assert_eq!(0xcafeu16.encode_into(&mut buf), 3);
+        assert_eq!(buf, [254, 149, 3, 0]);
+        assert_eq!(0xcafeu16.encode_to_array(), ([254, 149, 3], 3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn encode_into_too_small() {
+        let mut buf = [0; 2];
+        0xcafeu16.encode_into(&mut buf);
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_to_array_i64(val: i64) -> bool {
+            let (array, len) = val.encode_to_array();
+            array.len() == 10 && array[..len] == val.to_varint()[..]
+        }
+    }
+
+    #[test]
+    fn max_encoded_len() {
+        assert_eq!(u8::MAX_ENCODED_LEN, 2);
+        assert_eq!(u16::MAX_ENCODED_LEN, 3);
+        assert_eq!(u32::MAX_ENCODED_LEN, 5);
+        assert_eq!(u64::MAX_ENCODED_LEN, 10);
+        assert_eq!(u128::MAX_ENCODED_LEN, 19);
+        assert_eq!(i8::MAX_ENCODED_LEN, 2);
+        assert_eq!(i64::MAX_ENCODED_LEN, 10);
+        assert_eq!(SignExtended::<i32>::MAX_ENCODED_LEN, 10);
+        assert_eq!(u64::MAX.encoded_len(), u64::MAX_ENCODED_LEN);
+        assert_eq!(i16::MIN.encoded_len(), i16::MAX_ENCODED_LEN);
+        assert_eq!(SignExtended(-1i8).encoded_len(), 10);
+        assert_eq!(encoded_len(0), 1);
+        assert_eq!(encoded_len(127), 1);
+        assert_eq!(encoded_len(128), 2);
+    }
+
+    quickcheck! {
+        fn encoded_len_u128(val: u128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            encoded_len(val) == encode_to_array(val).1
+        }
+    }
+
+    quickcheck! {
+        fn encoded_len_i64(val: i64) -> bool {
+            val.encoded_len() == val.encode_to_array().1
+                && SignExtended(val).encoded_len() == SignExtended(val).encode_to_array().1
+        }
+    }
+
     quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_into_u128(val: u128) -> bool {
+            let mut buf = [0; 32];
+            let len = encode_into(val, &mut buf);
+            buf[..len] == encode(val)[..] && decode(&buf[..len]) == val
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_decode_i8(val: i8) -> bool {
+            val == i8::from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
         fn encode_decode_i16(val: i16) -> bool {
             val == i16::from_varint(&(val.to_varint()))
         }
     }
 
     quickcheck! {
+        #[cfg(feature = "alloc")]
         fn encode_decode_i32(val: i32) -> bool {
             val == i32::from_varint(&(val.to_varint()))
         }
     }
 
     quickcheck! {
+        #[cfg(feature = "alloc")]
         fn encode_decode_i64(val: i64) -> bool {
             val == i64::from_varint(&(val.to_varint()))
         }
     }
 
     quickcheck! {
+        #[cfg(feature = "alloc")]
         fn encode_decode_u16(val: u16) -> bool {
             val == u16::from_varint(&(val.to_varint()))
         }
     }
 
     quickcheck! {
+        #[cfg(feature = "alloc")]
         fn encode_decode_u32(val: u32) -> bool {
             val == u32::from_varint(&(val.to_varint()))
         }
     }
     quickcheck! {
+        #[cfg(feature = "alloc")]
         fn encode_decode_u64(val: u64) -> bool {
             val == u64::from_varint(&(val.to_varint()))
         }
     }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_decode_i128(val: i128) -> bool {
+            val == i128::from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_decode_isize(val: isize) -> bool {
+            val == isize::from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_decode_u8(val: u8) -> bool {
+            val == u8::from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_decode_u128(val: u128) -> bool {
+            val == u128::from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn encode_decode_usize(val: usize) -> bool {
+            val == usize::from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        fn try_encode_decode_i8(val: i8) -> bool {
+            let (array, len) = val.encode_to_array();
+            Ok((val, len)) == i8::try_from_varint_with_len(&array)
+        }
+    }
+
+    quickcheck! {
+        fn try_encode_decode_i128(val: i128) -> bool {
+            let (array, len) = val.encode_to_array();
+            Ok((val, len)) == i128::try_from_varint_with_len(&array)
+        }
+    }
+
+    quickcheck! {
+        fn try_encode_decode_u128(val: u128) -> bool {
+            let (array, len) = val.encode_to_array();
+            Ok((val, len)) == u128::try_from_varint_with_len(&array)
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn try_encode_decode_i64(val: i64) -> bool {
+            Ok(val) == i64::try_from_varint(&(val.to_varint()))
+        }
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn try_encode_decode_u64(val: u64) -> bool {
+            Ok(val) == u64::try_from_varint(&(val.to_varint()))
+        }
+    }
+
+    #[test]
+    fn try_decode_empty() {
+        assert_eq!(try_decode(&[]), Err(DecodeError::Empty));
+    }
+
+    #[test]
+    fn try_decode_truncated() {
+        assert_eq!(try_decode(&[254, 149]), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn try_decode_too_long() {
+        assert_eq!(try_decode(&[0x80; 20]), Err(DecodeError::TooLong));
+        assert_eq!(try_decode(&[0x80; 19]), Err(DecodeError::TooLong));
+    }
+
+    #[test]
+    fn try_decode_overflow() {
+        let mut data = [0xff; 19];
+        data[18] = 0x03;
+        assert_eq!(try_decode(&data), Ok(u128::MAX));
+        data[18] = 0x04;
+        assert_eq!(
+            try_decode(&data),
+            Err(DecodeError::Overflow { target_bits: 128 })
+        );
+    }
+
+    fn zigzag(val: i128) -> [u8; MAX_VARINT_LEN] {
+        varint(((val << 1) ^ (val >> 127)) as u128)
+    }
+
+    #[test]
+    fn try_decode_narrow_overflow() {
+        assert_eq!(
+            u16::try_from_varint(&[0x80, 0x80, 0x04]),
+            Err(DecodeError::Overflow { target_bits: 16 })
+        );
+        assert_eq!(u16::try_from_varint(&[0xff, 0xff, 0x03]), Ok(u16::MAX));
+        assert_eq!(
+            i16::try_from_varint(&zigzag(-32769)),
+            Err(DecodeError::Overflow { target_bits: 16 })
+        );
+        assert_eq!(i16::try_from_varint(&zigzag(-32768)), Ok(i16::MIN));
+    }
+
+    quickcheck! {
+        fn overflow_u16(val: u128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            match u16::try_from_varint(&varint(val)) {
+                Ok(decoded) => val <= u16::MAX as u128 && decoded as u128 == val,
+                Err(e) => val > u16::MAX as u128 && e == DecodeError::Overflow { target_bits: 16 },
+            }
+        }
+    }
+
+    quickcheck! {
+        fn overflow_u32(val: u128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            match u32::try_from_varint(&varint(val)) {
+                Ok(decoded) => val <= u32::MAX as u128 && decoded as u128 == val,
+                Err(e) => val > u32::MAX as u128 && e == DecodeError::Overflow { target_bits: 32 },
+            }
+        }
+    }
+
+    quickcheck! {
+        fn overflow_u64(val: u128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            match u64::try_from_varint(&varint(val)) {
+                Ok(decoded) => val <= u64::MAX as u128 && decoded as u128 == val,
+                Err(e) => val > u64::MAX as u128 && e == DecodeError::Overflow { target_bits: 64 },
+            }
+        }
+    }
+
+    quickcheck! {
+        fn overflow_i16(val: i128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            let in_range = val >= i16::MIN as i128 && val <= i16::MAX as i128;
+            match i16::try_from_varint(&zigzag(val)) {
+                Ok(decoded) => in_range && decoded as i128 == val,
+                Err(e) => !in_range && e == DecodeError::Overflow { target_bits: 16 },
+            }
+        }
+    }
+
+    quickcheck! {
+        fn overflow_i32(val: i128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            let in_range = val >= i32::MIN as i128 && val <= i32::MAX as i128;
+            match i32::try_from_varint(&zigzag(val)) {
+                Ok(decoded) => in_range && decoded as i128 == val,
+                Err(e) => !in_range && e == DecodeError::Overflow { target_bits: 32 },
+            }
+        }
+    }
+
+    quickcheck! {
+        fn overflow_i64(val: i128, shift: u8) -> bool {
+            let val = val >> (shift % 128);
+            let in_range = val >= i64::MIN as i128 && val <= i64::MAX as i128;
+            match i64::try_from_varint(&zigzag(val)) {
+                Ok(decoded) => in_range && decoded as i128 == val,
+                Err(e) => !in_range && e == DecodeError::Overflow { target_bits: 64 },
+            }
+        }
+    }
+
+    #[test]
+    fn zigzag_protobuf() {
+        assert_eq!(0i32.zigzag_encode(), 0);
+        assert_eq!((-1i32).zigzag_encode(), 1);
+        assert_eq!(1i32.zigzag_encode(), 2);
+        assert_eq!(i32::MAX.zigzag_encode(), 0xfffffffe);
+        assert_eq!(i32::MIN.zigzag_encode(), 0xffffffff);
+        assert_eq!(i64::zigzag_decode(0xffffffffffffffff), i64::MIN);
+    }
+
+    quickcheck! {
+        fn zigzag_i64(val: i64) -> bool {
+            i64::zigzag_decode(val.zigzag_encode()) == val
+        }
+    }
+
+    #[test]
+    fn sign_extended_protobuf() {
+        let (array, len) = SignExtended(150i32).encode_to_array();
+        assert_eq!(&array[..len], &[0x96, 0x01]);
+        let (array, len) = SignExtended(i32::MIN).encode_to_array();
+        assert_eq!(
+            &array[..len],
+            &[0x80, 0x80, 0x80, 0x80, 0xf8, 0xff, 0xff, 0xff, 0xff, 0x01]
+        );
+        assert_eq!(
+            SignExtended::<i32>::try_from_varint(&array[..len]),
+            Ok(SignExtended(i32::MIN))
+        );
+        let (array, len) = SignExtended(-1i128).encode_to_array();
+        assert_eq!(&array[..len], &varint(u128::MAX)[..]);
+    }
+
+    #[test]
+    fn sign_extended_overflow() {
+        let (array, len) = SignExtended(i64::from(i32::MIN) - 1).encode_to_array();
+        assert_eq!(
+            SignExtended::<i32>::try_from_varint(&array[..len]),
+            Err(DecodeError::Overflow { target_bits: 32 })
+        );
+        assert_eq!(
+            SignExtended::<i64>::try_from_varint(&varint(1 << 64)),
+            Err(DecodeError::Overflow { target_bits: 64 })
+        );
+    }
+
+    quickcheck! {
+        fn sign_extended_i32(val: i32) -> bool {
+            let (array, len) = SignExtended(val).encode_to_array();
+            SignExtended::<i32>::try_from_varint_with_len(&array) == Ok((SignExtended(val), len))
+                && (len == 10) == (val < 0)
+        }
+    }
+
+    quickcheck! {
+        fn sign_extended_i64(val: i64) -> bool {
+            let (array, len) = SignExtended(val).encode_to_array();
+            SignExtended::<i64>::try_from_varint_with_len(&array) == Ok((SignExtended(val), len))
+                && array[..len] == varint(val as u64 as u128)[..len]
+        }
+    }
+
+    #[test]
+    fn try_decode_ignores_trailing_bytes() {
+        assert_eq!(try_decode(&[172, 2, 0xff]), Ok(300));
+    }
+
+    #[test]
+    fn decode_with_len_concatenated() {
+        let data = [172, 2, 1, 254, 149, 3];
+        assert_eq!(decode_with_len(&data), (300, 2));
+        assert_eq!(decode_with_len(&data[2..]), (1, 1));
+        assert_eq!(u16::from_varint_with_len(&data[3..]), (0xcafe, 3));
+        assert_eq!(decode_with_len(&[0x80, 0x80]), (0, 2));
+        assert_eq!(decode_with_len(&[0x80; 20]), (0, 19));
+        assert_eq!(decode_with_len(&[]), (0, 0));
+    }
+
+    quickcheck! {
+        #[cfg(feature = "alloc")]
+        fn try_decode_with_len_concatenated(values: Vec<i32>) -> bool {
+            let mut data = Vec::new();
+            for value in &values {
+                data.extend(value.to_varint());
+            }
+            let mut offset = 0;
+            for value in &values {
+                match i32::try_from_varint_with_len(&data[offset..]) {
+                    Ok((decoded, len)) if decoded == *value => offset += len,
+                    _ => return false,
+                }
+            }
+            offset == data.len()
+        }
+    }
 }
@@ -0,0 +1,192 @@
+//! Encoding of integer slices as consecutive varints, the layout of protobuf packed
+//! repeated fields.
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::VarIntEncode;
+#[cfg(feature = "alloc")]
+use crate::{DecodeError, VarIntDecode, VarIntIter};
+
+/// Returns the number of bytes needed to encode `values` as consecutive varints.
+pub fn packed_len<T: VarIntEncode>(values: &[T]) -> usize {
+    values.iter().map(VarIntEncode::encoded_len).sum()
+}
+
+/// Encodes `values` as consecutive varints into the start of `buf` and returns the
+/// number of bytes written.
+///
+/// Panics when `buf` is smaller than `packed_len(values)`.
+pub fn encode_packed_into<T: VarIntEncode>(values: &[T], buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    for value in values {
+        len += value.encode_into(&mut buf[len..]);
+    }
+    len
+}
+
+/// Encodes `values` as consecutive varints.
+///
+/// ```
+///    assert_eq!(varint::encode_packed(&[3u32, 270, 86942]), vec![3, 142, 2, 158, 167, 5]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed<T: VarIntEncode>(values: &[T]) -> Vec<u8> {
+    let mut output = alloc::vec![0; packed_len(values)];
+    encode_packed_into(values, &mut output);
+    output
+}
+
+/// Encodes `u32` values as consecutive varints.
+///
+/// Produces the same bytes as `encode_packed`, but encodes with SSE4.1 or AVX2 when
+/// the CPU supports them, detected like in `decode_packed_u32`.
+///
+/// ```
+///    assert_eq!(varint::encode_packed_u32(&[3, 270, 86942]), vec![3, 142, 2, 158, 167, 5]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_packed_u32(values: &[u32]) -> Vec<u8> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    return encode_packed_fast(values, crate::simd::packed::encode_u32);
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    return encode_packed(values);
+}
+
+/// Encodes `u64` values as consecutive varints.
+///
+/// Produces the same bytes as `encode_packed`, accelerated like `encode_packed_u32`.
+#[cfg(feature = "alloc")]
+pub fn encode_packed_u64(values: &[u64]) -> Vec<u8> {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    return encode_packed_fast(values, crate::simd::packed::encode_u64);
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    return encode_packed(values);
+}
+
+/// Number of values `encode_packed_fast` encodes between two resizes of its output.
+#[cfg(all(feature = "alloc", any(target_arch = "x86", target_arch = "x86_64")))]
+const ENCODE_CHUNK: usize = 1024;
+
+#[cfg(all(feature = "alloc", any(target_arch = "x86", target_arch = "x86_64")))]
+fn encode_packed_fast<T: VarIntEncode>(
+    values: &[T],
+    kernel: fn(&[T], &mut [u8]) -> (usize, usize),
+) -> Vec<u8> {
+    // the output grows by the longest encoding of every chunk, as computing the
+    // exact length first takes as long as encoding small values
+    let mut output = Vec::new();
+    for chunk in values.chunks(ENCODE_CHUNK) {
+        let start = output.len();
+        let max_len = chunk.len() * T::MAX_ENCODED_LEN + crate::simd::packed::ENCODE_SLACK;
+        output.resize(start + max_len, 0);
+        let (encoded, mut len) = kernel(chunk, &mut output[start..]);
+        len += encode_packed_into(&chunk[encoded..], &mut output[start + len..]);
+        output.truncate(start + len);
+    }
+    output
+}
+
+/// Decodes a byte array of consecutive varints.
+///
+/// Use `VarIntIter` to decode the values without collecting them.
+///
+/// ```
+///    assert_eq!(varint::decode_packed(&[3, 142, 2, 158, 167, 5]), Ok(vec![3u32, 270, 86942]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decode_packed<T: VarIntDecode>(data: &[u8]) -> Result<Vec<T>, DecodeError> {
+    VarIntIter::new(data).collect()
+}
+
+/// Decodes a byte array of consecutive varints into `u32` values.
+///
+/// Produces the same result as `decode_packed::<u32>`, but decodes with SSE4.1 or
+/// AVX2 when the CPU supports them. Support is detected at runtime with the `std`
+/// feature, and at compile time from the enabled target features otherwise.
+///
+/// ```
+///    assert_eq!(varint::decode_packed_u32(&[3, 142, 2, 158, 167, 5]), Ok(vec![3, 270, 86942]));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn decode_packed_u32(data: &[u8]) -> Result<Vec<u32>, DecodeError> {
+    decode_packed_fast(data)
+}
+
+/// Decodes a byte array of consecutive varints into `u64` values.
+///
+/// Produces the same result as `decode_packed::<u64>`, accelerated like
+/// `decode_packed_u32`.
+#[cfg(feature = "alloc")]
+pub fn decode_packed_u64(data: &[u8]) -> Result<Vec<u64>, DecodeError> {
+    decode_packed_fast(data)
+}
+
+#[cfg(feature = "alloc")]
+fn decode_packed_fast<T: VarIntDecode + VarIntEncode + From<u32>>(
+    data: &[u8],
+) -> Result<Vec<T>, DecodeError> {
+    // the fewest values the data can hold, as counting the last bytes of the
+    // varints costs a pass over the data; the output grows past it for short ones
+    let mut output = Vec::with_capacity(data.len() / T::MAX_ENCODED_LEN);
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let pos = crate::simd::packed::decode(data, &mut output);
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    let pos = 0;
+    for value in VarIntIter::new(&data[pos..]) {
+        output.push(value?);
+    }
+    Ok(output)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_packed_truncated() {
+        assert_eq!(decode_packed::<u32>(&[3, 142]), Err(DecodeError::Truncated));
+        assert_eq!(decode_packed::<u32>(&[]), Ok(vec![]));
+    }
+
+    quickcheck! {
+        fn packed_i64(values: Vec<i64>) -> bool {
+            let packed = encode_packed(&values);
+            let expected: Vec<u8> = values.iter().flat_map(|value| value.to_varint()).collect();
+            packed.len() == packed_len(&values)
+                && packed == expected
+                && decode_packed::<i64>(&packed) == Ok(values)
+        }
+    }
+
+    quickcheck! {
+        fn packed_u32(values: Vec<u32>) -> bool {
+            decode_packed::<u32>(&encode_packed(&values)) == Ok(values)
+        }
+    }
+
+    #[test]
+    fn packed_fast_chunks() {
+        // several output chunks, the last one shorter
+        let values: Vec<u64> = (0..2500u64).map(|i| i * i * i * 1_000_003).collect();
+        let narrow: Vec<u32> = values.iter().map(|&value| value as u32).collect();
+        assert_eq!(encode_packed_u64(&values), encode_packed(&values));
+        assert_eq!(encode_packed_u32(&narrow), encode_packed(&narrow));
+    }
+
+    quickcheck! {
+        fn packed_fast_encode(values: Vec<u64>) -> bool {
+            let narrow: Vec<u32> = values.iter().map(|&value| value as u32).collect();
+            encode_packed_u64(&values) == encode_packed(&values)
+                && encode_packed_u32(&narrow) == encode_packed(&narrow)
+        }
+    }
+
+    quickcheck! {
+        fn packed_fast(values: Vec<u64>, data: Vec<u8>) -> bool {
+            let packed = encode_packed(&values);
+            decode_packed_u64(&packed) == Ok(values)
+                && decode_packed_u32(&data) == decode_packed::<u32>(&data)
+                && decode_packed_u64(&data) == decode_packed::<u64>(&data)
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Run-length encoding of integer sequences with varint values and run lengths.
+//!
+//! Two layouts are supported, both made of varints, the values encoded with their
+//! `VarIntEncode` implementation and the lengths as `u64`:
+//!
+//! - `encode` writes every run of equal values as a pair of the value and the
+//!   length of the run.
+//! - `encode_hybrid` writes runs of at least 3 equal values as a header of
+//!   `length << 1` followed by the value, and groups the values in between in
+//!   literal runs of a header of `length << 1 | 1` followed by the values, so
+//!   sequences without repetitions cost one header instead of a length per value.
+//!
+//! Decoding is lazy: `decode` and `decode_hybrid` return an iterator expanding the
+//! runs as it goes, so long runs are never materialized.
+//!
+//! ```
+//! # #[cfg(feature = "alloc")]
+//! # {
+//!    use varint::rle;
+//!
+//!    let values = [7u32, 7, 7, 7, 1, 2, 3];
+//!    let pairs = rle::encode(&values);
+//!    assert_eq!(pairs, [7, 4, 1, 1, 2, 1, 3, 1]);
+//!    let hybrid = rle::encode_hybrid(&values);
+//!    assert_eq!(hybrid, [8, 7, 7, 1, 2, 3]);
+//!
+//!    let decoded: Result<Vec<u32>, _> = rle::decode_hybrid(&hybrid).collect();
+//!    assert_eq!(decoded, Ok(values.to_vec()));
+//! # }
+//! ```
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+
+#[cfg(feature = "alloc")]
+use crate::VarIntEncode;
+use crate::{DecodeError, VarIntDecode};
+
+/// Minimum length of a run written as a repeated run by `encode_hybrid`.
+#[cfg(feature = "alloc")]
+const MIN_REPEATED_RUN: usize = 3;
+
+#[cfg(feature = "alloc")]
+fn put<V: VarIntEncode>(output: &mut Vec<u8>, value: V) {
+    let (array, len) = value.encode_to_array();
+    output.extend_from_slice(&array.as_ref()[..len]);
+}
+
+/// Returns the length of the run of values equal to the first one.
+#[cfg(feature = "alloc")]
+fn run_len<T: PartialEq>(values: &[T]) -> usize {
+    values
+        .iter()
+        .take_while(|&value| *value == values[0])
+        .count()
+}
+
+/// Encodes `values` as pairs of a value and the length of its run.
+#[cfg(feature = "alloc")]
+pub fn encode<T: VarIntEncode + PartialEq + Copy>(values: &[T]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut i = 0;
+    while i < values.len() {
+        let len = run_len(&values[i..]);
+        put(&mut output, values[i]);
+        put(&mut output, len as u64);
+        i += len;
+    }
+    output
+}
+
+/// Encodes `values` as repeated runs and literal runs.
+#[cfg(feature = "alloc")]
+pub fn encode_hybrid<T: VarIntEncode + PartialEq + Copy>(values: &[T]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let flush_literals = |output: &mut Vec<u8>, literals: &[T]| {
+        if !literals.is_empty() {
+            put(output, (literals.len() as u64) << 1 | 1);
+            for &value in literals {
+                put(output, value);
+            }
+        }
+    };
+
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i < values.len() {
+        let len = run_len(&values[i..]);
+        if len >= MIN_REPEATED_RUN {
+            flush_literals(&mut output, &values[literal_start..i]);
+            put(&mut output, (len as u64) << 1);
+            put(&mut output, values[i]);
+            literal_start = i + len;
+        }
+        i += len;
+    }
+    flush_literals(&mut output, &values[literal_start..]);
+    output
+}
+
+/// Returns an iterator over the values encoded with `encode`.
+pub fn decode<T: VarIntDecode + Copy>(data: &[u8]) -> Decoder<'_, T> {
+    Decoder::new(data, false)
+}
+
+/// Returns an iterator over the values encoded with `encode_hybrid`.
+pub fn decode_hybrid<T: VarIntDecode + Copy>(data: &[u8]) -> Decoder<'_, T> {
+    Decoder::new(data, true)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Run<T> {
+    Repeated { value: T, remaining: u64 },
+    Literal { remaining: u64 },
+}
+
+/// Iterator expanding run-length encoded values, one `Result` per value.
+///
+/// Like `VarIntIter`, the iterator ends after an error. Data ending inside a run
+/// yields `Truncated`.
+///
+/// ```
+///    // a run of 2^40 zeros, decoded lazily
+///    let data = [0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x20];
+///    let mut decoder = varint::rle::decode::<u64>(&data);
+///    assert_eq!(decoder.nth(1_000_000), Some(Ok(0)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Decoder<'a, T> {
+    data: &'a [u8],
+    offset: usize,
+    hybrid: bool,
+    run: Option<Run<T>>,
+    failed: bool,
+}
+
+impl<'a, T: VarIntDecode + Copy> Decoder<'a, T> {
+    fn new(data: &'a [u8], hybrid: bool) -> Decoder<'a, T> {
+        Decoder {
+            data,
+            offset: 0,
+            hybrid,
+            run: None,
+            failed: false,
+        }
+    }
+
+    /// Returns the offset of the next varint from the start of the data.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    fn read<V: VarIntDecode>(&mut self) -> Result<V, DecodeError> {
+        match V::try_from_varint_with_len(&self.data[self.offset..]) {
+            Ok((value, len)) => {
+                self.offset += len;
+                Ok(value)
+            }
+            // the data ends inside a run
+            Err(DecodeError::Empty) => Err(DecodeError::Truncated),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_run(&mut self) -> Result<Run<T>, DecodeError> {
+        if !self.hybrid {
+            let value = self.read()?;
+            let remaining = self.read()?;
+            return Ok(Run::Repeated { value, remaining });
+        }
+        let header: u64 = self.read()?;
+        let remaining = header >> 1;
+        if header & 1 == 1 {
+            Ok(Run::Literal { remaining })
+        } else {
+            let value = self.read()?;
+            Ok(Run::Repeated { value, remaining })
+        }
+    }
+
+    fn next_value(&mut self) -> Option<Result<T, DecodeError>> {
+        loop {
+            match self.run {
+                Some(Run::Repeated {
+                    value,
+                    ref mut remaining,
+                }) if *remaining > 0 => {
+                    *remaining -= 1;
+                    return Some(Ok(value));
+                }
+                Some(Run::Literal { ref mut remaining }) if *remaining > 0 => {
+                    *remaining -= 1;
+                    return Some(self.read());
+                }
+                _ if self.offset == self.data.len() => return None,
+                _ => match self.read_run() {
+                    Ok(run) => self.run = Some(run),
+                    Err(err) => return Some(Err(err)),
+                },
+            }
+        }
+    }
+}
+
+impl<T: VarIntDecode + Copy> Iterator for Decoder<'_, T> {
+    type Item = Result<T, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<T, DecodeError>> {
+        if self.failed {
+            return None;
+        }
+        let next = self.next_value();
+        self.failed = matches!(next, Some(Err(_)));
+        next
+    }
+}
+
+impl<T: VarIntDecode + Copy> FusedIterator for Decoder<'_, T> {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    fn collect<T: VarIntDecode + Copy>(decoder: Decoder<T>) -> Result<Vec<T>, DecodeError> {
+        decoder.collect()
+    }
+
+    #[test]
+    fn layouts() {
+        assert_eq!(encode::<u32>(&[]), []);
+        assert_eq!(encode_hybrid::<u32>(&[]), []);
+        assert_eq!(encode(&[-1i32, -1]), [1, 2]);
+        assert_eq!(encode_hybrid(&[-1i32, -1]), [5, 1, 1]);
+        assert_eq!(encode_hybrid(&[1u8, 2, 2, 2, 3]), [3, 1, 6, 2, 3, 3]);
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(collect(decode::<u32>(&[7])), Err(DecodeError::Truncated));
+        assert_eq!(
+            collect(decode::<u32>(&[7, 0x80])),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            collect(decode_hybrid::<u32>(&[5, 1])),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            collect(decode_hybrid::<u32>(&[4])),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(
+            collect(decode::<u8>(&[0x80, 2, 1])),
+            Err(DecodeError::Overflow { target_bits: 8 })
+        );
+        // empty runs are skipped
+        assert_eq!(collect(decode::<u8>(&[1, 0, 2, 1])), Ok(vec![2]));
+
+        let mut decoder = decode::<u32>(&[7, 2, 8]);
+        assert_eq!(decoder.next(), Some(Ok(7)));
+        assert_eq!(decoder.next(), Some(Ok(7)));
+        assert_eq!(decoder.next(), Some(Err(DecodeError::Truncated)));
+        assert_eq!(decoder.next(), None);
+    }
+
+    /// Expands pairs of a value and a run length.
+    fn runs(runs: &[(i64, u8)]) -> Vec<i64> {
+        runs.iter()
+            .flat_map(|&(value, len)| std::iter::repeat_n(value, len as usize % 8))
+            .collect()
+    }
+
+    quickcheck! {
+        fn rle_i64(pairs: Vec<(i64, u8)>) -> bool {
+            let values = runs(&pairs);
+            collect(decode(&encode(&values))) == Ok(values.clone())
+                && collect(decode_hybrid(&encode_hybrid(&values))) == Ok(values)
+        }
+    }
+
+    quickcheck! {
+        fn rle_hybrid_no_runs(values: Vec<u64>) -> bool {
+            let mut values = values;
+            values.dedup();
+            let encoded = encode_hybrid(&values);
+            let header = if values.is_empty() { 0 } else { (values.len() as u64 * 2 + 1).to_varint().len() };
+            encoded.len() == header + crate::packed_len(&values)
+                && collect(decode_hybrid(&encoded)) == Ok(values)
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Serde support to serialize integers as varint bytes.
+//!
+//! Use the `unsigned` and `signed` modules with `#[serde(with = "...")]`, or wrap
+//! values in the `VarInt` and `ZigZag` newtypes. The varint is serialized with
+//! `serialize_bytes`; deserialization accepts bytes as well as a sequence of `u8`
+//! for formats without a native byte string type.
+//!
+//! ```
+//!    use serde::{Deserialize, Serialize};
+//!
+//!    #[derive(Serialize, Deserialize)]
+//!    struct Record {
+//!        #[serde(with = "varint::serde::unsigned")]
+//!        id: u64,
+//!        #[serde(with = "varint::serde::signed")]
+//!        offset: i32,
+//!        count: varint::serde::VarInt<u32>,
+//!    }
+//! ```
+use core::fmt;
+use core::marker::PhantomData;
+
+use ::serde::de::{self, Deserializer, SeqAccess, Visitor};
+use ::serde::{Deserialize, Serialize, Serializer};
+
+use crate::{VarIntDecode, VarIntEncode, MAX_VARINT_LEN};
+
+/// Serializes and deserializes an integer as a varint.
+///
+/// Meant for unsigned integers; signed integers are ZigZag encoded by their
+/// `VarIntEncode` implementation.
+pub mod unsigned {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: VarIntEncode,
+        S: Serializer,
+    {
+        let (array, len) = value.encode_to_array();
+        serializer.serialize_bytes(&array.as_ref()[..len])
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: VarIntDecode,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_bytes(VarIntVisitor(PhantomData))
+    }
+}
+
+/// Serializes and deserializes a signed integer as a ZigZag encoded varint.
+pub mod signed {
+    use super::*;
+    use crate::ZigZag as ZigZagEncode;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: ZigZagEncode + Copy,
+        T::Unsigned: VarIntEncode,
+        S: Serializer,
+    {
+        super::unsigned::serialize(&value.zigzag_encode(), serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: ZigZagEncode,
+        T::Unsigned: VarIntDecode,
+        D: Deserializer<'de>,
+    {
+        super::unsigned::deserialize(deserializer).map(T::zigzag_decode)
+    }
+}
+
+/// Integer that is serialized as a varint, using its `VarIntEncode` implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct VarInt<T>(pub T);
+
+impl<T: VarIntEncode> Serialize for VarInt<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        unsigned::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, T: VarIntDecode> Deserialize<'de> for VarInt<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        unsigned::deserialize(deserializer).map(VarInt)
+    }
+}
+
+/// Signed integer that is serialized as a ZigZag encoded varint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ZigZag<T>(pub T);
+
+impl<T> Serialize for ZigZag<T>
+where
+    T: crate::ZigZag + Copy,
+    T::Unsigned: VarIntEncode,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        signed::serialize(&self.0, serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for ZigZag<T>
+where
+    T: crate::ZigZag,
+    T::Unsigned: VarIntDecode,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        signed::deserialize(deserializer).map(ZigZag)
+    }
+}
+
+struct VarIntVisitor<T>(PhantomData<T>);
+
+impl<'de, T: VarIntDecode> Visitor<'de> for VarIntVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("varint bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<T, E> {
+        let (value, len) = T::try_from_varint_with_len(v).map_err(E::custom)?;
+        if len != v.len() {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        Ok(value)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        let mut buf = [0u8; MAX_VARINT_LEN];
+        let mut len = 0;
+        while let Some(b) = seq.next_element::<u8>()? {
+            if len == MAX_VARINT_LEN {
+                return Err(de::Error::invalid_length(len + 1, &self));
+            }
+            buf[len] = b;
+            len += 1;
+        }
+        self.visit_bytes(&buf[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_test::{assert_de_tokens, assert_de_tokens_error, assert_tokens, Token};
+
+    #[test]
+    fn varint_tokens() {
+        assert_tokens(&VarInt(300u32), &[Token::Bytes(&[172, 2])]);
+        assert_tokens(&VarInt(-300i32), &[Token::Bytes(&[215, 4])]);
+        assert_tokens(&ZigZag(-300i32), &[Token::Bytes(&[215, 4])]);
+    }
+
+    #[test]
+    fn varint_from_seq() {
+        assert_de_tokens(
+            &VarInt(300u32),
+            &[
+                Token::Seq { len: Some(2) },
+                Token::U8(172),
+                Token::U8(2),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn varint_errors() {
+        assert_de_tokens_error::<VarInt<u32>>(&[Token::Bytes(&[172])], "truncated varint");
+        assert_de_tokens_error::<VarInt<u8>>(
+            &[Token::Bytes(&[172, 2])],
+            "varint overflows a 8 bit integer",
+        );
+        assert_de_tokens_error::<VarInt<u32>>(
+            &[Token::Bytes(&[172, 2, 0])],
+            "invalid length 3, expected varint bytes",
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        #[serde(with = "unsigned")]
+        id: u64,
+        #[serde(with = "signed")]
+        offset: i32,
+    }
+
+    #[test]
+    fn with_modules() {
+        assert_tokens(
+            &Record {
+                id: 0xcafe,
+                offset: -1,
+            },
+            &[
+                Token::Struct {
+                    name: "Record",
+                    len: 2,
+                },
+                Token::Str("id"),
+                Token::Bytes(&[254, 149, 3]),
+                Token::Str("offset"),
+                Token::Bytes(&[1]),
+                Token::StructEnd,
+            ],
+        );
+    }
+}
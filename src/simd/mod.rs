@@ -0,0 +1,24 @@
+//! x86 SIMD kernels, used when the CPU supports them.
+//!
+//! Every kernel processes the part of its input it can handle with full vector
+//! loads and stores, and returns how far it got; the caller finishes with the
+//! scalar implementation, which also reports every error.
+#[cfg(feature = "alloc")]
+pub(crate) mod packed;
+pub(crate) mod stream_vbyte;
+pub(crate) mod varint_g8iu;
+
+/// Returns whether AVX2 and SSE4.1 are available, detected at runtime with the
+/// `std` feature and from the enabled target features otherwise.
+pub(crate) fn features() -> (bool, bool) {
+    #[cfg(feature = "std")]
+    return (
+        is_x86_feature_detected!("avx2"),
+        is_x86_feature_detected!("sse4.1"),
+    );
+    #[cfg(not(feature = "std"))]
+    return (
+        cfg!(target_feature = "avx2"),
+        cfg!(target_feature = "sse4.1"),
+    );
+}
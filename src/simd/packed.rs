@@ -0,0 +1,773 @@
+//! SSE4.1 and AVX2 encoding and decoding of consecutive LEB128 varints.
+//!
+//! Decoding follows the Masked VByte algorithm of Plaisance, Kurz and Lemire. The
+//! continuation bits of the next 12 bytes index a table describing how many
+//! complete varints of at most 4 bytes start there, and a shuffle gathering their
+//! bytes into 16 or 32 bit lanes. Varints of 5 bytes or more, and anything the
+//! table cannot describe, are decoded by the scalar `VarIntDecode` implementation,
+//! which also reports every error. Stretches of mostly long varints are handed to
+//! the scalar decoder a window at a time. The kernels only run while at least 16
+//! bytes remain; the caller decodes the tail.
+//!
+//! Encoding spreads the 7 bit groups of each value over the bytes of a 64 bit lane,
+//! sets the continuation bits from the nonzero groups, and stores the whole lane,
+//! advancing by the length of the varint. Blocks of values below 128 are narrowed
+//! to bytes directly.
+use alloc::vec::Vec;
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use super::features;
+use crate::{VarIntDecode, VarIntEncode};
+
+/// Decoding of the varints starting in one 12 byte window.
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Moves the bytes of varint `i` into lane `i`, zeroing the other bytes.
+    shuffle: [u8; 16],
+    /// Number of varints decoded, 0 when the first varint needs the scalar decoder.
+    count: u8,
+    /// Number of bytes in the decoded varints.
+    consumed: u8,
+    /// Whether the lanes are 32 bits wide (varints of up to 4 bytes) instead of 16
+    /// bits (varints of up to 2 bytes).
+    wide: bool,
+}
+
+const EMPTY: Entry = Entry {
+    shuffle: [0x80; 16],
+    count: 0,
+    consumed: 0,
+    wide: false,
+};
+
+/// Number of leading varints of at most `max_len` bytes, up to `max_count`.
+const fn prefix_count(lens: &[u8; 12], n: usize, max_len: u8, max_count: usize) -> usize {
+    let mut count = 0;
+    while count < n && count < max_count && lens[count] <= max_len {
+        count += 1;
+    }
+    count
+}
+
+const fn build_entry(mask: usize) -> Entry {
+    // lengths of the varints completed within the window
+    let mut lens = [0u8; 12];
+    let mut n = 0;
+    let mut start = 0;
+    let mut i = 0;
+    while i < 12 {
+        if (mask >> i) & 1 == 0 {
+            lens[n] = (i + 1 - start) as u8;
+            n += 1;
+            start = i + 1;
+        }
+        i += 1;
+    }
+
+    let narrow = prefix_count(&lens, n, 2, 6);
+    let wide_count = prefix_count(&lens, n, 4, 4);
+    let (count, wide, lane) = if narrow >= wide_count {
+        (narrow, false, 2)
+    } else {
+        (wide_count, true, 4)
+    };
+
+    let mut entry = EMPTY;
+    let mut offset = 0;
+    let mut j = 0;
+    while j < count {
+        let mut b = 0;
+        while b < lens[j] as usize {
+            entry.shuffle[j * lane + b] = (offset + b) as u8;
+            b += 1;
+        }
+        offset += lens[j] as usize;
+        j += 1;
+    }
+    entry.count = count as u8;
+    entry.consumed = offset as u8;
+    entry.wide = wide;
+    entry
+}
+
+static TABLE: [Entry; 4096] = {
+    let mut table = [EMPTY; 4096];
+    let mut mask = 0;
+    while mask < 4096 {
+        table[mask] = build_entry(mask);
+        mask += 1;
+    }
+    table
+};
+
+/// Decodes the varints at the start of `data[pos..]`, which holds at least 16 bytes.
+///
+/// Returns the position after the decoded varints, which is `pos` when the scalar
+/// decoder failed at `pos`. Errors are left to the caller's scalar decoder.
+#[target_feature(enable = "sse4.1")]
+unsafe fn decode_block<T: VarIntDecode + From<u32>>(
+    data: &[u8],
+    pos: usize,
+    output: &mut Vec<T>,
+) -> usize {
+    let input = _mm_loadu_si128(data.as_ptr().add(pos) as *const __m128i);
+    let mask = _mm_movemask_epi8(input) as usize;
+
+    if mask == 0 {
+        // 16 single byte varints
+        let mut values = [0u32; 16];
+        let out = values.as_mut_ptr() as *mut __m128i;
+        _mm_storeu_si128(out, _mm_cvtepu8_epi32(input));
+        _mm_storeu_si128(out.add(1), _mm_cvtepu8_epi32(_mm_srli_si128(input, 4)));
+        _mm_storeu_si128(out.add(2), _mm_cvtepu8_epi32(_mm_srli_si128(input, 8)));
+        _mm_storeu_si128(out.add(3), _mm_cvtepu8_epi32(_mm_srli_si128(input, 12)));
+        output.extend(values.iter().map(|&value| T::from(value)));
+        return pos + 16;
+    }
+
+    let entry = &TABLE[mask & 0xfff];
+    if entry.count == 0 {
+        // decode long varints without going back to the table while they last
+        let mut pos = pos;
+        loop {
+            let (value, len) = match T::try_from_varint_with_len(&data[pos..]) {
+                Ok(decoded) => decoded,
+                Err(_) => return pos,
+            };
+            output.push(value);
+            pos += len;
+            match data.get(pos..pos + 4) {
+                Some(next) if next.iter().all(|b| b & 0x80 != 0) => {}
+                _ => return pos,
+            }
+        }
+    }
+
+    let shuffle = _mm_loadu_si128(entry.shuffle.as_ptr() as *const __m128i);
+    let lanes = _mm_shuffle_epi8(input, shuffle);
+    let mut values = [0u32; 8];
+    let out = values.as_mut_ptr() as *mut __m128i;
+    if entry.wide {
+        let b0 = _mm_and_si128(lanes, _mm_set1_epi32(0x7f));
+        let b1 = _mm_srli_epi32(_mm_and_si128(lanes, _mm_set1_epi32(0x7f00)), 1);
+        let b2 = _mm_srli_epi32(_mm_and_si128(lanes, _mm_set1_epi32(0x7f_0000)), 2);
+        let b3 = _mm_srli_epi32(_mm_and_si128(lanes, _mm_set1_epi32(0x7f00_0000)), 3);
+        let value = _mm_or_si128(_mm_or_si128(b0, b1), _mm_or_si128(b2, b3));
+        _mm_storeu_si128(out, value);
+    } else {
+        let b0 = _mm_and_si128(lanes, _mm_set1_epi16(0x7f));
+        let b1 = _mm_srli_epi16(_mm_and_si128(lanes, _mm_set1_epi16(0x7f00)), 1);
+        let words = _mm_or_si128(b0, b1);
+        _mm_storeu_si128(out, _mm_cvtepu16_epi32(words));
+        _mm_storeu_si128(out.add(1), _mm_cvtepu16_epi32(_mm_srli_si128(words, 8)));
+    }
+    let count = entry.count as usize;
+    output.extend(values[..count].iter().map(|&value| T::from(value)));
+    pos + entry.consumed as usize
+}
+
+/// Number of bytes between two checks of the length of the decoded varints.
+const WINDOW: usize = 256;
+
+/// Average length of the recently decoded varints, which decides when to decode
+/// the next `WINDOW` bytes with the scalar decoder.
+struct Window {
+    pos: usize,
+    len: usize,
+}
+
+impl Window {
+    fn new(pos: usize, output_len: usize) -> Window {
+        Window {
+            pos,
+            len: output_len,
+        }
+    }
+
+    /// Returns whether the varints decoded since the last check, every `WINDOW`
+    /// bytes, average more than 4 bytes. Such varints mostly go through the scalar
+    /// decoder, and interleaving it with the kernels is slower than giving it the
+    /// next `WINDOW` bytes alone.
+    fn long_varints(&mut self, pos: usize, output_len: usize) -> bool {
+        if pos - self.pos < WINDOW {
+            return false;
+        }
+        let long = pos - self.pos > 4 * (output_len - self.len);
+        self.pos = pos;
+        self.len = output_len;
+        long
+    }
+}
+
+/// Decodes the varints starting in the next `WINDOW` bytes of `data[pos..]` with the
+/// scalar decoder.
+///
+/// Returns the position after them, or the position of the varint the scalar
+/// decoder failed on.
+fn decode_window<T: VarIntDecode>(
+    data: &[u8],
+    mut pos: usize,
+    output: &mut Vec<T>,
+) -> Result<usize, usize> {
+    let end = pos + WINDOW;
+    while pos < end && pos < data.len() {
+        let (value, len) = T::try_from_varint_with_len(&data[pos..]).map_err(|_| pos)?;
+        output.push(value);
+        pos += len;
+    }
+    Ok(pos)
+}
+
+/// Decodes varints from `data` with SSE4.1 and returns the number of bytes consumed.
+#[target_feature(enable = "sse4.1")]
+pub(crate) unsafe fn decode_sse41<T: VarIntDecode + From<u32>>(
+    data: &[u8],
+    output: &mut Vec<T>,
+) -> usize {
+    let mut window = Window::new(0, output.len());
+    let mut pos = 0;
+    while pos + 16 <= data.len() {
+        if window.long_varints(pos, output.len()) {
+            match decode_window(data, pos, output) {
+                Ok(next) => pos = next,
+                Err(next) => return next,
+            }
+            window = Window::new(pos, output.len());
+            continue;
+        }
+        let next = decode_block(data, pos, output);
+        if next == pos {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+/// Decodes varints from `data` with AVX2 and returns the number of bytes consumed.
+///
+/// Runs of 32 single byte varints are widened with AVX2, everything else goes
+/// through the SSE4.1 kernel.
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn decode_avx2<T: VarIntDecode + From<u32>>(
+    data: &[u8],
+    output: &mut Vec<T>,
+) -> usize {
+    let mut window = Window::new(0, output.len());
+    let mut pos = 0;
+    while pos + 16 <= data.len() {
+        if window.long_varints(pos, output.len()) {
+            match decode_window(data, pos, output) {
+                Ok(next) => pos = next,
+                Err(next) => return next,
+            }
+            window = Window::new(pos, output.len());
+            continue;
+        }
+        if pos + 32 <= data.len() {
+            let input = _mm256_loadu_si256(data.as_ptr().add(pos) as *const __m256i);
+            if _mm256_movemask_epi8(input) == 0 {
+                let low = _mm256_castsi256_si128(input);
+                let high = _mm256_extracti128_si256(input, 1);
+                let mut values = [0u32; 32];
+                let out = values.as_mut_ptr() as *mut __m256i;
+                _mm256_storeu_si256(out, _mm256_cvtepu8_epi32(low));
+                _mm256_storeu_si256(out.add(1), _mm256_cvtepu8_epi32(_mm_srli_si128(low, 8)));
+                _mm256_storeu_si256(out.add(2), _mm256_cvtepu8_epi32(high));
+                _mm256_storeu_si256(out.add(3), _mm256_cvtepu8_epi32(_mm_srli_si128(high, 8)));
+                output.extend(values.iter().map(|&value| T::from(value)));
+                pos += 32;
+                continue;
+            }
+        }
+        let next = decode_block(data, pos, output);
+        if next == pos {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+/// Decodes varints from `data` with the best kernel the CPU supports and returns
+/// the number of bytes consumed, 0 when no kernel is available.
+pub(crate) fn decode<T: VarIntDecode + From<u32>>(data: &[u8], output: &mut Vec<T>) -> usize {
+    let (avx2, sse41) = features();
+    if avx2 {
+        unsafe { decode_avx2(data, output) }
+    } else if sse41 {
+        unsafe { decode_sse41(data, output) }
+    } else {
+        0
+    }
+}
+
+/// Number of bytes the encoding kernels may write past the end of their output.
+pub(crate) const ENCODE_SLACK: usize = 8;
+
+/// Spreads the low 56 bits of each 64 bit lane into groups of 7 bits, one per byte,
+/// and sets the continuation bit of every byte followed by a nonzero group.
+///
+/// Returns the spread lanes and the movemask of their continuation bits.
+#[target_feature(enable = "sse4.1")]
+unsafe fn spread_sse41(x: __m128i) -> (__m128i, u32) {
+    let group = [
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 7)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 14)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 21)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 28)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 35)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 42)),
+        _mm_and_si128(x, _mm_set1_epi64x(0x7f << 49)),
+    ];
+    let mut s = group[0];
+    s = _mm_or_si128(s, _mm_slli_epi64(group[1], 1));
+    s = _mm_or_si128(s, _mm_slli_epi64(group[2], 2));
+    s = _mm_or_si128(s, _mm_slli_epi64(group[3], 3));
+    s = _mm_or_si128(s, _mm_slli_epi64(group[4], 4));
+    s = _mm_or_si128(s, _mm_slli_epi64(group[5], 5));
+    s = _mm_or_si128(s, _mm_slli_epi64(group[6], 6));
+    s = _mm_or_si128(s, _mm_slli_epi64(group[7], 7));
+
+    // a byte continues when any byte above it in the lane is nonzero
+    let nonzero = _mm_andnot_si128(_mm_cmpeq_epi8(s, _mm_setzero_si128()), _mm_set1_epi8(-1));
+    let mut above = _mm_srli_epi64(nonzero, 8);
+    above = _mm_or_si128(above, _mm_srli_epi64(above, 8));
+    above = _mm_or_si128(above, _mm_srli_epi64(above, 16));
+    above = _mm_or_si128(above, _mm_srli_epi64(above, 32));
+    let cont = _mm_and_si128(above, _mm_set1_epi8(-128));
+    (_mm_or_si128(s, cont), _mm_movemask_epi8(cont) as u32)
+}
+
+/// Spreads like `spread_sse41`, for four lanes.
+#[target_feature(enable = "avx2")]
+unsafe fn spread_avx2(x: __m256i) -> (__m256i, u32) {
+    let group = [
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 7)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 14)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 21)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 28)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 35)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 42)),
+        _mm256_and_si256(x, _mm256_set1_epi64x(0x7f << 49)),
+    ];
+    let mut s = group[0];
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[1], 1));
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[2], 2));
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[3], 3));
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[4], 4));
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[5], 5));
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[6], 6));
+    s = _mm256_or_si256(s, _mm256_slli_epi64(group[7], 7));
+
+    let nonzero = _mm256_andnot_si256(
+        _mm256_cmpeq_epi8(s, _mm256_setzero_si256()),
+        _mm256_set1_epi8(-1),
+    );
+    let mut above = _mm256_srli_epi64(nonzero, 8);
+    above = _mm256_or_si256(above, _mm256_srli_epi64(above, 8));
+    above = _mm256_or_si256(above, _mm256_srli_epi64(above, 16));
+    above = _mm256_or_si256(above, _mm256_srli_epi64(above, 32));
+    let cont = _mm256_and_si256(above, _mm256_set1_epi8(-128));
+    (_mm256_or_si256(s, cont), _mm256_movemask_epi8(cont) as u32)
+}
+
+/// Stores the spread lanes one after the other, each advancing `pos` by the length
+/// of its varint.
+fn put_lanes(lanes: &[u64], mask: u32, buf: &mut [u8], mut pos: usize) -> usize {
+    for (i, lane) in lanes.iter().enumerate() {
+        buf[pos..pos + 8].copy_from_slice(&lane.to_le_bytes());
+        pos += ((mask >> (8 * i)) & 0xff).count_ones() as usize + 1;
+    }
+    pos
+}
+
+/// Encodes two values below 2^56 into `buf` at `pos` and returns the new position.
+#[target_feature(enable = "sse4.1")]
+unsafe fn put_sse41(x: __m128i, buf: &mut [u8], pos: usize) -> usize {
+    let (s, mask) = spread_sse41(x);
+    let mut lanes = [0u64; 2];
+    _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, s);
+    put_lanes(&lanes, mask, buf, pos)
+}
+
+/// Encodes four values below 2^56 into `buf` at `pos` and returns the new position.
+#[target_feature(enable = "avx2")]
+unsafe fn put_avx2(x: __m256i, buf: &mut [u8], pos: usize) -> usize {
+    let (s, mask) = spread_avx2(x);
+    let mut lanes = [0u64; 4];
+    _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, s);
+    put_lanes(&lanes, mask, buf, pos)
+}
+
+/// Returns the number of values the encoding kernels handle next, out of `remaining`:
+/// a `block` checked at once for values below 128 while enough remain, one `group`
+/// after.
+///
+/// Blocks failing the check are encoded as a whole, so mostly large values do not
+/// pay for a check every `group`.
+fn block_len(remaining: usize, block: usize, group: usize) -> usize {
+    if remaining >= block {
+        block
+    } else {
+        group
+    }
+}
+
+/// Encodes 16 `u32` values into `buf` at `pos` when they are all below 128.
+#[target_feature(enable = "sse4.1")]
+unsafe fn put_small_u32_sse41(values: &[u32], buf: &mut [u8], pos: usize) -> bool {
+    let input = values[..16].as_ptr() as *const __m128i;
+    let a = _mm_loadu_si128(input);
+    let b = _mm_loadu_si128(input.add(1));
+    let c = _mm_loadu_si128(input.add(2));
+    let d = _mm_loadu_si128(input.add(3));
+    let all = _mm_or_si128(_mm_or_si128(a, b), _mm_or_si128(c, d));
+    if _mm_testz_si128(all, _mm_set1_epi32(!0x7f)) == 0 {
+        return false;
+    }
+    let bytes = _mm_packus_epi16(_mm_packus_epi32(a, b), _mm_packus_epi32(c, d));
+    _mm_storeu_si128(buf[pos..pos + 16].as_mut_ptr() as *mut __m128i, bytes);
+    true
+}
+
+/// Encodes 16 `u64` values into `buf` at `pos` when they are all below 128.
+#[target_feature(enable = "sse4.1")]
+unsafe fn put_small_u64_sse41(values: &[u64], buf: &mut [u8], pos: usize) -> bool {
+    let input = values[..16].as_ptr() as *const __m128i;
+    let mut v = [_mm_setzero_si128(); 8];
+    let mut all = _mm_setzero_si128();
+    for (i, lane) in v.iter_mut().enumerate() {
+        *lane = _mm_loadu_si128(input.add(i));
+        all = _mm_or_si128(all, *lane);
+    }
+    if _mm_testz_si128(all, _mm_set1_epi64x(!0x7f)) == 0 {
+        return false;
+    }
+    // the high halves of the lanes are zero, so the first two narrowings leave
+    // every value followed by a zero byte, which the last one drops
+    let low = _mm_packus_epi16(_mm_packus_epi32(v[0], v[1]), _mm_packus_epi32(v[2], v[3]));
+    let high = _mm_packus_epi16(_mm_packus_epi32(v[4], v[5]), _mm_packus_epi32(v[6], v[7]));
+    let bytes = _mm_packus_epi16(low, high);
+    _mm_storeu_si128(buf[pos..pos + 16].as_mut_ptr() as *mut __m128i, bytes);
+    true
+}
+
+/// Encodes `values` into `buf` with SSE4.1 and returns the number of values
+/// encoded and of bytes written.
+///
+/// `buf` must hold the encoded values plus `ENCODE_SLACK` bytes.
+#[target_feature(enable = "sse4.1")]
+pub(crate) unsafe fn encode_u32_sse41(values: &[u32], buf: &mut [u8]) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    while i + 4 <= values.len() {
+        let block = block_len(values.len() - i, 16, 4);
+        if block == 16 && put_small_u32_sse41(&values[i..], buf, pos) {
+            i += 16;
+            pos += 16;
+            continue;
+        }
+        for chunk in values[i..i + block].chunks_exact(4) {
+            let v = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            pos = put_sse41(_mm_cvtepu32_epi64(v), buf, pos);
+            pos = put_sse41(_mm_cvtepu32_epi64(_mm_srli_si128(v, 8)), buf, pos);
+        }
+        i += block;
+    }
+    (i, pos)
+}
+
+/// Encodes `values` into `buf` with AVX2, like `encode_u32_sse41`.
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn encode_u32_avx2(values: &[u32], buf: &mut [u8]) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    while i + 8 <= values.len() {
+        let block = block_len(values.len() - i, 32, 8);
+        if block == 32 {
+            let input = values[i..i + 32].as_ptr() as *const __m256i;
+            let a = _mm256_loadu_si256(input);
+            let b = _mm256_loadu_si256(input.add(1));
+            let c = _mm256_loadu_si256(input.add(2));
+            let d = _mm256_loadu_si256(input.add(3));
+            let all = _mm256_or_si256(_mm256_or_si256(a, b), _mm256_or_si256(c, d));
+            if _mm256_testz_si256(all, _mm256_set1_epi32(!0x7f)) != 0 {
+                // packing works within 128 bit halves, the permutation restores
+                // the order of the 4 byte groups
+                let bytes =
+                    _mm256_packus_epi16(_mm256_packus_epi32(a, b), _mm256_packus_epi32(c, d));
+                let order = _mm256_setr_epi32(0, 4, 1, 5, 2, 6, 3, 7);
+                let bytes = _mm256_permutevar8x32_epi32(bytes, order);
+                _mm256_storeu_si256(buf[pos..pos + 32].as_mut_ptr() as *mut __m256i, bytes);
+                i += 32;
+                pos += 32;
+                continue;
+            }
+        }
+        for chunk in values[i..i + block].chunks_exact(8) {
+            let v = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            pos = put_avx2(_mm256_cvtepu32_epi64(_mm256_castsi256_si128(v)), buf, pos);
+            pos = put_avx2(
+                _mm256_cvtepu32_epi64(_mm256_extracti128_si256(v, 1)),
+                buf,
+                pos,
+            );
+        }
+        i += block;
+    }
+    (i, pos)
+}
+
+/// Encodes `values` into `buf` with SSE4.1, like `encode_u32_sse41`.
+///
+/// Values of 2^56 and above are encoded by the scalar implementation.
+#[target_feature(enable = "sse4.1")]
+pub(crate) unsafe fn encode_u64_sse41(values: &[u64], buf: &mut [u8]) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    while i + 2 <= values.len() {
+        let block = block_len(values.len() - i, 16, 2);
+        if block == 16 && put_small_u64_sse41(&values[i..], buf, pos) {
+            i += 16;
+            pos += 16;
+            continue;
+        }
+        for chunk in values[i..i + block].chunks_exact(2) {
+            let v = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            if _mm_testz_si128(v, _mm_set1_epi64x(!0 << 56)) != 0 {
+                pos = put_sse41(v, buf, pos);
+            } else {
+                for value in chunk {
+                    pos += value.encode_into(&mut buf[pos..]);
+                }
+            }
+        }
+        i += block;
+    }
+    (i, pos)
+}
+
+/// Encodes `values` into `buf` with AVX2, like `encode_u64_sse41`.
+#[target_feature(enable = "avx2")]
+pub(crate) unsafe fn encode_u64_avx2(values: &[u64], buf: &mut [u8]) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    while i + 4 <= values.len() {
+        let block = block_len(values.len() - i, 16, 4);
+        if block == 16 && put_small_u64_sse41(&values[i..], buf, pos) {
+            i += 16;
+            pos += 16;
+            continue;
+        }
+        for chunk in values[i..i + block].chunks_exact(4) {
+            let v = _mm256_loadu_si256(chunk.as_ptr() as *const __m256i);
+            if _mm256_testz_si256(v, _mm256_set1_epi64x(!0 << 56)) != 0 {
+                pos = put_avx2(v, buf, pos);
+            } else {
+                for value in chunk {
+                    pos += value.encode_into(&mut buf[pos..]);
+                }
+            }
+        }
+        i += block;
+    }
+    (i, pos)
+}
+
+/// Encodes `values` into `buf` with the best kernel the CPU supports and returns
+/// the number of values encoded and of bytes written, `(0, 0)` when no kernel is
+/// available.
+pub(crate) fn encode_u32(values: &[u32], buf: &mut [u8]) -> (usize, usize) {
+    let (avx2, sse41) = features();
+    if avx2 {
+        unsafe { encode_u32_avx2(values, buf) }
+    } else if sse41 {
+        unsafe { encode_u32_sse41(values, buf) }
+    } else {
+        (0, 0)
+    }
+}
+
+/// Encodes `values` into `buf` like `encode_u32`.
+pub(crate) fn encode_u64(values: &[u64], buf: &mut [u8]) -> (usize, usize) {
+    let (avx2, sse41) = features();
+    if avx2 {
+        unsafe { encode_u64_avx2(values, buf) }
+    } else if sse41 {
+        unsafe { encode_u64_sse41(values, buf) }
+    } else {
+        (0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::shifted;
+    use crate::{decode_packed, encode_packed, DecodeError};
+
+    /// Decodes with `kernel` and the scalar decoder for the tail.
+    fn decode_with<T: VarIntDecode + From<u32> + PartialEq + core::fmt::Debug>(
+        data: &[u8],
+        kernel: unsafe fn(&[u8], &mut Vec<T>) -> usize,
+    ) -> Result<Vec<T>, DecodeError> {
+        let mut output = Vec::new();
+        let pos = unsafe { kernel(data, &mut output) };
+        // the kernel outputs exactly the values of the bytes it consumed
+        assert_eq!(decode_packed(&data[..pos]).as_ref(), Ok(&output));
+        for value in crate::VarIntIter::new(&data[pos..]) {
+            output.push(value?);
+        }
+        Ok(output)
+    }
+
+    fn check(data: &[u8]) -> bool {
+        let expected32 = decode_packed::<u32>(data);
+        let expected64 = decode_packed::<u64>(data);
+        let mut ok = true;
+        if is_x86_feature_detected!("sse4.1") {
+            ok &= decode_with(data, decode_sse41::<u32>) == expected32;
+            ok &= decode_with(data, decode_sse41::<u64>) == expected64;
+        }
+        if is_x86_feature_detected!("avx2") {
+            ok &= decode_with(data, decode_avx2::<u32>) == expected32;
+            ok &= decode_with(data, decode_avx2::<u64>) == expected64;
+        }
+        ok
+    }
+
+    #[test]
+    fn table() {
+        // 3 single byte varints, then one of 2 bytes and 7 more single bytes
+        let entry = &TABLE[0b0000_0000_1000];
+        assert!(!entry.wide);
+        assert_eq!(entry.count, 6);
+        assert_eq!(entry.consumed, 7);
+        // a 3 byte varint first
+        let entry = &TABLE[0b1111_1111_1011];
+        assert!(entry.wide);
+        assert_eq!(entry.count, 1);
+        assert_eq!(entry.consumed, 3);
+        // a 5 byte varint first
+        assert_eq!(TABLE[0b1111].count, 0);
+    }
+
+    #[test]
+    fn window() {
+        let mut window = Window::new(0, 10);
+        assert!(!window.long_varints(WINDOW - 1, 10));
+        assert!(!window.long_varints(WINDOW, 10 + WINDOW / 4));
+        assert!(window.long_varints(2 * WINDOW, 10 + WINDOW / 4 + WINDOW / 5));
+    }
+
+    #[test]
+    fn adversarial() {
+        assert!(check(&[0x80; 64]));
+        assert!(check(&[0xff; 40]));
+        assert!(check(&[0; 100]));
+        // zero padded varints longer than necessary
+        let mut data = vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        data.extend_from_slice(&[1; 32]);
+        assert!(check(&data));
+        // overflow of u32 but not u64 in the middle of small values
+        let mut data = vec![5; 20];
+        data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x1f]);
+        data.extend_from_slice(&[5; 20]);
+        assert!(check(&data));
+        // an overflow of u32 after long varints decoded by the scalar decoder
+        let mut data = [0xff, 0xff, 0xff, 0xff, 0x01].repeat(3);
+        data.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x7f]);
+        data.extend_from_slice(&[1; 20]);
+        assert!(check(&data));
+        // long varints followed by small values, which go back to the kernels
+        let mut data = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01].repeat(2 * WINDOW / 7);
+        data.extend_from_slice(&[1; 2 * WINDOW]);
+        assert!(check(&data));
+        let mut output = Vec::<u64>::new();
+        if is_x86_feature_detected!("sse4.1") {
+            assert!(unsafe { decode_sse41(&data, &mut output) } + 16 > data.len());
+        }
+        if is_x86_feature_detected!("avx2") {
+            output.clear();
+            assert!(unsafe { decode_avx2(&data, &mut output) } + 16 > data.len());
+        }
+        // truncated at the end
+        let mut data = vec![0x81; 3];
+        data.extend_from_slice(&[1; 30]);
+        data.push(0x81);
+        assert!(check(&data));
+    }
+
+    /// Encodes with `kernel` and the scalar encoder for the tail.
+    fn encode_with<T: VarIntEncode>(
+        values: &[T],
+        kernel: unsafe fn(&[T], &mut [u8]) -> (usize, usize),
+    ) -> Vec<u8> {
+        let mut buf = vec![0; crate::packed_len(values) + ENCODE_SLACK];
+        let (n, mut pos) = unsafe { kernel(values, &mut buf) };
+        pos += crate::encode_packed_into(&values[n..], &mut buf[pos..]);
+        buf.truncate(pos);
+        buf
+    }
+
+    fn check_encode(values: &[u64]) -> bool {
+        let narrow: Vec<u32> = values.iter().map(|&value| value as u32).collect();
+        let expected32 = encode_packed(&narrow);
+        let expected64 = encode_packed(values);
+        let mut ok = true;
+        if is_x86_feature_detected!("sse4.1") {
+            ok &= encode_with(&narrow, encode_u32_sse41) == expected32;
+            ok &= encode_with(values, encode_u64_sse41) == expected64;
+        }
+        if is_x86_feature_detected!("avx2") {
+            ok &= encode_with(&narrow, encode_u32_avx2) == expected32;
+            ok &= encode_with(values, encode_u64_avx2) == expected64;
+        }
+        ok
+    }
+
+    #[test]
+    fn encode_edges() {
+        assert!(check_encode(&[0; 100]));
+        assert!(check_encode(&[127; 50]));
+        assert!(check_encode(&[u64::MAX; 50]));
+        let mut values: Vec<u64> = (0..64).map(|shift| 1 << shift).collect();
+        values.extend((1..64).map(|shift| (1 << shift) - 1));
+        values.extend_from_slice(&[5; 40]);
+        assert!(check_encode(&values));
+    }
+
+    quickcheck! {
+        fn encode_differential(values: Vec<u64>, shifts: Vec<u8>) -> bool {
+            let values = shifted(&values, &shifts);
+            check_encode(&values)
+        }
+    }
+
+    quickcheck! {
+        fn differential_values(values: Vec<u64>, shifts: Vec<u8>) -> bool {
+            let values = shifted(&values, &shifts);
+            check(&encode_packed(&values))
+        }
+    }
+
+    quickcheck! {
+        fn differential_bytes(data: Vec<u8>) -> bool {
+            check(&data)
+        }
+    }
+
+    quickcheck! {
+        fn differential_mostly_small(data: Vec<u8>) -> bool {
+            // set the continuation bit on about one byte in eight
+            let data: Vec<u8> = data
+                .iter()
+                .map(|b| if b % 8 == 0 { b | 0x80 } else { b & 0x7f })
+                .collect();
+            check(&data)
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! SSE4.1 encoding and decoding of the Stream VByte format.
+//!
+//! Every control byte describes four values, so one shuffle from a table indexed by
+//! the control byte moves their data bytes into or out of 32 bit lanes.
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use crate::stream_vbyte::Mode;
+
+/// Shuffles and data lengths of the four values of every control byte.
+struct Tables {
+    /// Moves the data bytes into 32 bit lanes, zeroing the other bytes.
+    decode: [[u8; 16]; 256],
+    /// Moves the used bytes of the 32 bit lanes to the front.
+    encode: [[u8; 16]; 256],
+    len: [u8; 256],
+}
+
+static TABLES: Tables = {
+    let mut tables = Tables {
+        decode: [[0x80; 16]; 256],
+        encode: [[0x80; 16]; 256],
+        len: [0; 256],
+    };
+    let mut control = 0;
+    while control < 256 {
+        let mut offset = 0;
+        let mut lane = 0;
+        while lane < 4 {
+            let len = ((control >> (2 * lane)) & 3) + 1;
+            let mut b = 0;
+            while b < len {
+                tables.decode[control][4 * lane + b] = (offset + b) as u8;
+                tables.encode[control][offset + b] = (4 * lane + b) as u8;
+                b += 1;
+            }
+            offset += len;
+            lane += 1;
+        }
+        tables.len[control] = offset as u8;
+        control += 1;
+    }
+    tables
+};
+
+/// Encodes groups of four values while 16 bytes of `data` remain and returns the
+/// number of values encoded and of data bytes written.
+#[target_feature(enable = "sse4.1")]
+pub(crate) unsafe fn encode(
+    values: &[u32],
+    control: &mut [u8],
+    data: &mut [u8],
+    mode: Mode,
+    prev: u32,
+) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    let mut prev = _mm_set1_epi32(prev as i32);
+    while i + 4 <= values.len() && pos + 16 <= data.len() {
+        let input = _mm_loadu_si128(values[i..i + 4].as_ptr() as *const __m128i);
+        let v = match mode {
+            Mode::Plain => input,
+            // subtract the previous lane, the last value of the group before for lane 0
+            Mode::Delta => _mm_sub_epi32(input, _mm_alignr_epi8(input, prev, 12)),
+            Mode::ZigZag => _mm_xor_si128(_mm_slli_epi32(input, 1), _mm_srai_epi32(input, 31)),
+        };
+        prev = input;
+
+        // a value takes as many bytes as the position of its last nonzero byte
+        let zero = _mm_movemask_epi8(_mm_cmpeq_epi8(v, _mm_setzero_si128())) as u32;
+        let mut key = 0;
+        for lane in 0..4 {
+            let nonzero = !(zero >> (4 * lane)) & 0xf;
+            let code = (31 - (nonzero | 1).leading_zeros()) as usize;
+            key |= code << (2 * lane);
+        }
+
+        let shuffle = _mm_loadu_si128(TABLES.encode[key].as_ptr() as *const __m128i);
+        let bytes = _mm_shuffle_epi8(v, shuffle);
+        _mm_storeu_si128(data[pos..pos + 16].as_mut_ptr() as *mut __m128i, bytes);
+        control[i / 4] = key as u8;
+        pos += TABLES.len[key] as usize;
+        i += 4;
+    }
+    (i, pos)
+}
+
+/// Decodes groups of four values while 16 bytes of `data` remain and returns the
+/// number of values decoded and of data bytes read.
+#[target_feature(enable = "sse4.1")]
+pub(crate) unsafe fn decode(
+    control: &[u8],
+    data: &[u8],
+    values: &mut [u32],
+    mode: Mode,
+    prev: u32,
+) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    let mut prev = _mm_set1_epi32(prev as i32);
+    while i + 4 <= values.len() && pos + 16 <= data.len() {
+        let key = control[i / 4] as usize;
+        let input = _mm_loadu_si128(data[pos..pos + 16].as_ptr() as *const __m128i);
+        let shuffle = _mm_loadu_si128(TABLES.decode[key].as_ptr() as *const __m128i);
+        let v = _mm_shuffle_epi8(input, shuffle);
+        let output = match mode {
+            Mode::Plain => v,
+            Mode::Delta => {
+                // prefix sum of the lanes, added to the last value of the group before
+                let v = _mm_add_epi32(v, _mm_slli_si128(v, 4));
+                let v = _mm_add_epi32(v, _mm_slli_si128(v, 8));
+                prev = _mm_add_epi32(v, _mm_shuffle_epi32(prev, 0xff));
+                prev
+            }
+            Mode::ZigZag => {
+                let sign = _mm_sub_epi32(_mm_setzero_si128(), _mm_and_si128(v, _mm_set1_epi32(1)));
+                _mm_xor_si128(_mm_srli_epi32(v, 1), sign)
+            }
+        };
+        _mm_storeu_si128(values[i..i + 4].as_mut_ptr() as *mut __m128i, output);
+        pos += TABLES.len[key] as usize;
+        i += 4;
+    }
+    (i, pos)
+}
@@ -0,0 +1,75 @@
+//! SSE4.1 decoding of the Varint-G8IU format.
+//!
+//! The descriptor byte of a block indexes a table of two shuffles moving its data
+//! bytes into the 32 bit lanes of up to 8 values.
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+/// Decoding of the values of a block with a given descriptor.
+#[derive(Clone, Copy)]
+struct Entry {
+    /// Moves the bytes of value `i` into lane `i`, for values 0 to 3 and 4 to 7.
+    shuffle: [[u8; 16]; 2],
+    /// Number of values in the block.
+    count: u8,
+    /// Whether every value takes at most 4 bytes.
+    valid: bool,
+}
+
+static TABLE: [Entry; 256] = {
+    let mut table = [Entry {
+        shuffle: [[0x80; 16]; 2],
+        count: 0,
+        valid: true,
+    }; 256];
+    let mut descriptor = 0;
+    while descriptor < 256 {
+        let entry = &mut table[descriptor];
+        let mut start = 0;
+        let mut k = 0;
+        while k < 8 {
+            if (descriptor >> k) & 1 == 0 {
+                let len = k + 1 - start;
+                if len > 4 {
+                    entry.valid = false;
+                    break;
+                }
+                let lane = entry.count as usize;
+                let mut b = 0;
+                while b < len {
+                    entry.shuffle[lane / 4][4 * (lane % 4) + b] = (start + b) as u8;
+                    b += 1;
+                }
+                entry.count += 1;
+                start = k + 1;
+            }
+            k += 1;
+        }
+        descriptor += 1;
+    }
+    table
+};
+
+/// Decodes whole blocks while room for 8 values remains in `values` and returns
+/// the number of values decoded and of bytes read.
+#[target_feature(enable = "sse4.1")]
+pub(crate) unsafe fn decode(data: &[u8], values: &mut [u32]) -> (usize, usize) {
+    let (mut i, mut pos) = (0, 0);
+    while i + 8 <= values.len() && pos + 9 <= data.len() {
+        let entry = &TABLE[data[pos] as usize];
+        if !entry.valid {
+            break;
+        }
+        let input = _mm_loadl_epi64(data[pos + 1..pos + 9].as_ptr() as *const __m128i);
+        let out = values[i..i + 8].as_mut_ptr() as *mut __m128i;
+        let low = _mm_loadu_si128(entry.shuffle[0].as_ptr() as *const __m128i);
+        _mm_storeu_si128(out, _mm_shuffle_epi8(input, low));
+        let high = _mm_loadu_si128(entry.shuffle[1].as_ptr() as *const __m128i);
+        _mm_storeu_si128(out.add(1), _mm_shuffle_epi8(input, high));
+        i += entry.count as usize;
+        pos += 9;
+    }
+    (i, pos)
+}
@@ -0,0 +1,424 @@
+//! Stream VByte encoding of `u32` slices.
+//!
+//! Stream VByte stores every value in 1 to 4 little endian bytes, like LEB128 uses
+//! 1 to 5, but moves the lengths out of the data bytes into a separate stream of
+//! control bytes, which lets SIMD decoders handle four values per control byte with
+//! a single shuffle. The SSE4.1 kernels are used when the CPU supports them,
+//! detected like in `decode_packed_u32`.
+//!
+//! ## Layout
+//!
+//! The layout is the one of the reference C library by Lemire et al.
+//! (`streamvbyte_encode` and `streamvbyte_decode`). For `count` values:
+//!
+//! - `(count + 3) / 4` control bytes, each holding the lengths of 4 values as 2 bit
+//!   codes, the first value in the least significant bits. The code is the length
+//!   in bytes minus one. Unused codes of the last control byte are 0.
+//! - The data bytes: the values in order, each in as many little endian bytes as
+//!   its code says, at least one byte for 0.
+//!
+//! Like the other formats of the `codec` module, the encoding does not store the
+//! number of values.
+//!
+//! The delta variants encode the wrapping differences between consecutive values,
+//! starting from `prev`, like `streamvbyte_delta_encode`. The zigzag variants
+//! encode `i32` values ZigZag encoded, like `zigzag_encode` of
+//! `streamvbyte_zigzag.h`, followed by `streamvbyte_encode`.
+//!
+//! ```
+//!    use varint::stream_vbyte;
+//!
+//!    let values = [1, 256, 65536, 16777216, 5];
+//!    let mut buf = [0; stream_vbyte::max_encoded_len(5)];
+//!    let len = stream_vbyte::encode_into(&values, &mut buf);
+//!    assert_eq!(buf[..len], [0xe4, 0x00, 1, 0, 1, 0, 0, 1, 0, 0, 0, 1, 5]);
+//!
+//!    let mut decoded = [0; 5];
+//!    assert_eq!(stream_vbyte::decode_into(&buf[..len], &mut decoded), Ok(len));
+//!    assert_eq!(decoded, values);
+//! ```
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::codec::{decode_vec, encode_vec};
+use crate::{DecodeError, ZigZag};
+
+/// Transformation applied to the values before encoding and after decoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Plain,
+    Delta,
+    ZigZag,
+}
+
+impl Mode {
+    fn encode(self, value: u32, prev: u32) -> u32 {
+        match self {
+            Mode::Plain => value,
+            Mode::Delta => value.wrapping_sub(prev),
+            Mode::ZigZag => (value as i32).zigzag_encode(),
+        }
+    }
+
+    fn decode(self, value: u32, prev: u32) -> u32 {
+        match self {
+            Mode::Plain => value,
+            Mode::Delta => prev.wrapping_add(value),
+            Mode::ZigZag => i32::zigzag_decode(value) as u32,
+        }
+    }
+}
+
+/// Number of values converted between `i32` and `u32` at a time by the zigzag
+/// variants, a multiple of 4 so every chunk starts at a control byte.
+const ZIGZAG_CHUNK: usize = 256;
+
+/// Returns the number of control bytes for `count` values.
+const fn control_len(count: usize) -> usize {
+    count.div_ceil(4)
+}
+
+/// Returns the maximum number of bytes needed to encode `count` values.
+pub const fn max_encoded_len(count: usize) -> usize {
+    control_len(count) + 4 * count
+}
+
+/// Encodes `values` into the start of `buf` and returns the number of bytes
+/// written.
+///
+/// Panics when the encoded values do not fit in `buf`, like `Codec::encode_into`.
+pub fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+    encode_mode(values, buf, Mode::Plain, 0)
+}
+
+/// Encodes the differences between consecutive `values`, the first one relative
+/// to `prev`, like `encode_into`.
+pub fn encode_delta_into(values: &[u32], prev: u32, buf: &mut [u8]) -> usize {
+    encode_mode(values, buf, Mode::Delta, prev)
+}
+
+/// Encodes ZigZag encoded `values`, like `encode_into`.
+pub fn encode_zigzag_into(values: &[i32], buf: &mut [u8]) -> usize {
+    let (control, data) = buf.split_at_mut(control_len(values.len()));
+    let mut chunk = [0u32; ZIGZAG_CHUNK];
+    let mut pos = 0;
+    for (i, values) in values.chunks(ZIGZAG_CHUNK).enumerate() {
+        let chunk = &mut chunk[..values.len()];
+        for (unsigned, &value) in chunk.iter_mut().zip(values) {
+            *unsigned = value as u32;
+        }
+        let control = &mut control[i * ZIGZAG_CHUNK / 4..];
+        pos += encode_parts(chunk, control, &mut data[pos..], Mode::ZigZag, 0);
+    }
+    control.len() + pos
+}
+
+/// Decodes `values.len()` values from `data` and returns the number of bytes read.
+pub fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+    decode_mode(data, values, Mode::Plain, 0)
+}
+
+/// Decodes values encoded with `encode_delta_into`, like `decode_into`.
+pub fn decode_delta_into(data: &[u8], prev: u32, values: &mut [u32]) -> Result<usize, DecodeError> {
+    decode_mode(data, values, Mode::Delta, prev)
+}
+
+/// Decodes values encoded with `encode_zigzag_into`, like `decode_into`.
+pub fn decode_zigzag_into(data: &[u8], values: &mut [i32]) -> Result<usize, DecodeError> {
+    let control_len = control_len(values.len());
+    if data.len() < control_len {
+        return Err(DecodeError::Truncated);
+    }
+    let (control, data) = data.split_at(control_len);
+    let mut chunk = [0u32; ZIGZAG_CHUNK];
+    let mut pos = 0;
+    for (i, values) in values.chunks_mut(ZIGZAG_CHUNK).enumerate() {
+        let chunk = &mut chunk[..values.len()];
+        let control = &control[i * ZIGZAG_CHUNK / 4..];
+        pos += decode_parts(control, &data[pos..], chunk, Mode::ZigZag, 0)?;
+        for (value, &unsigned) in values.iter_mut().zip(chunk.iter()) {
+            *value = unsigned as i32;
+        }
+    }
+    Ok(control_len + pos)
+}
+
+/// Encodes `values`.
+#[cfg(feature = "alloc")]
+pub fn encode(values: &[u32]) -> Vec<u8> {
+    encode_vec(max_encoded_len(values.len()), |buf| {
+        encode_into(values, buf)
+    })
+}
+
+/// Encodes the differences between consecutive `values`, the first one relative
+/// to `prev`.
+///
+/// ```
+///    use varint::stream_vbyte;
+///
+///    let values = [1000, 1001, 1005, 1500];
+///    let encoded = stream_vbyte::encode_delta(&values, 1000);
+///    assert_eq!(encoded, [0x40, 0, 1, 4, 239, 1]);
+///    assert_eq!(stream_vbyte::decode_delta(&encoded, 4, 1000), Ok(values.to_vec()));
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encode_delta(values: &[u32], prev: u32) -> Vec<u8> {
+    encode_vec(max_encoded_len(values.len()), |buf| {
+        encode_delta_into(values, prev, buf)
+    })
+}
+
+/// Encodes ZigZag encoded `values`.
+#[cfg(feature = "alloc")]
+pub fn encode_zigzag(values: &[i32]) -> Vec<u8> {
+    encode_vec(max_encoded_len(values.len()), |buf| {
+        encode_zigzag_into(values, buf)
+    })
+}
+
+/// Decodes `count` values from `data`.
+///
+/// Bytes after the encoded values are ignored.
+#[cfg(feature = "alloc")]
+pub fn decode(data: &[u8], count: usize) -> Result<Vec<u32>, DecodeError> {
+    decode_vec(count, |values| decode_into(data, values))
+}
+
+/// Decodes `count` values encoded with `encode_delta`.
+#[cfg(feature = "alloc")]
+pub fn decode_delta(data: &[u8], count: usize, prev: u32) -> Result<Vec<u32>, DecodeError> {
+    decode_vec(count, |values| decode_delta_into(data, prev, values))
+}
+
+/// Decodes `count` values encoded with `encode_zigzag`.
+#[cfg(feature = "alloc")]
+pub fn decode_zigzag(data: &[u8], count: usize) -> Result<Vec<i32>, DecodeError> {
+    decode_vec(count, |values| decode_zigzag_into(data, values))
+}
+
+fn encode_mode(values: &[u32], buf: &mut [u8], mode: Mode, prev: u32) -> usize {
+    let (control, data) = buf.split_at_mut(control_len(values.len()));
+    control.len() + encode_parts(values, control, data, mode, prev)
+}
+
+/// Encodes `values` into the control bytes and data bytes and returns the number
+/// of data bytes written.
+fn encode_parts(
+    values: &[u32],
+    control: &mut [u8],
+    data: &mut [u8],
+    mode: Mode,
+    prev: u32,
+) -> usize {
+    let (start, pos) = encode_simd(values, control, data, mode, prev);
+    encode_scalar(values, start, control, data, pos, mode, prev)
+}
+
+/// Encodes `values[start..]` with the data starting at `pos` and returns the end of
+/// the data.
+fn encode_scalar(
+    values: &[u32],
+    start: usize,
+    control: &mut [u8],
+    data: &mut [u8],
+    mut pos: usize,
+    mode: Mode,
+    prev: u32,
+) -> usize {
+    let mut prev = if start > 0 { values[start - 1] } else { prev };
+    for (i, &value) in values.iter().enumerate().skip(start) {
+        let encoded = mode.encode(value, prev);
+        prev = value;
+        let len = (4 - encoded.leading_zeros() as usize / 8).max(1);
+        data[pos..pos + len].copy_from_slice(&encoded.to_le_bytes()[..len]);
+        pos += len;
+        if i % 4 == 0 {
+            control[i / 4] = 0;
+        }
+        control[i / 4] |= ((len - 1) << (2 * (i % 4))) as u8;
+    }
+    pos
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn encode_simd(
+    values: &[u32],
+    control: &mut [u8],
+    data: &mut [u8],
+    mode: Mode,
+    prev: u32,
+) -> (usize, usize) {
+    if crate::simd::features().1 {
+        unsafe { crate::simd::stream_vbyte::encode(values, control, data, mode, prev) }
+    } else {
+        (0, 0)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn encode_simd(_: &[u32], _: &mut [u8], _: &mut [u8], _: Mode, _: u32) -> (usize, usize) {
+    (0, 0)
+}
+
+fn decode_mode(
+    data: &[u8],
+    values: &mut [u32],
+    mode: Mode,
+    prev: u32,
+) -> Result<usize, DecodeError> {
+    let control_len = control_len(values.len());
+    if data.len() < control_len {
+        return Err(DecodeError::Truncated);
+    }
+    let (control, data) = data.split_at(control_len);
+    Ok(control_len + decode_parts(control, data, values, mode, prev)?)
+}
+
+/// Decodes `values` from the control bytes and data bytes and returns the number
+/// of data bytes read.
+fn decode_parts(
+    control: &[u8],
+    data: &[u8],
+    values: &mut [u32],
+    mode: Mode,
+    prev: u32,
+) -> Result<usize, DecodeError> {
+    let (start, pos) = decode_simd(control, data, values, mode, prev);
+    decode_scalar(control, data, pos, values, start, mode, prev)
+}
+
+/// Decodes `values[start..]` from the data starting at `pos` and returns the end
+/// of the data.
+fn decode_scalar(
+    control: &[u8],
+    data: &[u8],
+    mut pos: usize,
+    values: &mut [u32],
+    start: usize,
+    mode: Mode,
+    prev: u32,
+) -> Result<usize, DecodeError> {
+    let mut prev = if start > 0 { values[start - 1] } else { prev };
+    for i in start..values.len() {
+        let len = ((control[i / 4] >> (2 * (i % 4))) & 3) as usize + 1;
+        let bytes = data.get(pos..pos + len).ok_or(DecodeError::Truncated)?;
+        let mut le = [0u8; 4];
+        le[..len].copy_from_slice(bytes);
+        prev = mode.decode(u32::from_le_bytes(le), prev);
+        values[i] = prev;
+        pos += len;
+    }
+    Ok(pos)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn decode_simd(
+    control: &[u8],
+    data: &[u8],
+    values: &mut [u32],
+    mode: Mode,
+    prev: u32,
+) -> (usize, usize) {
+    if crate::simd::features().1 {
+        unsafe { crate::simd::stream_vbyte::decode(control, data, values, mode, prev) }
+    } else {
+        (0, 0)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn decode_simd(_: &[u8], _: &[u8], _: &mut [u32], _: Mode, _: u32) -> (usize, usize) {
+    (0, 0)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::test_util::shifted;
+    use std::vec::Vec;
+
+    /// Encodes like `encode_mode`, without the SIMD kernel.
+    fn encode_scalar_only(values: &[u32], mode: Mode, prev: u32) -> Vec<u8> {
+        encode_vec(max_encoded_len(values.len()), |buf| {
+            let (control, data) = buf.split_at_mut(control_len(values.len()));
+            control.len() + encode_scalar(values, 0, control, data, 0, mode, prev)
+        })
+    }
+
+    /// Decodes like `decode_mode`, without the SIMD kernel.
+    fn decode_scalar_only(
+        data: &[u8],
+        count: usize,
+        mode: Mode,
+        prev: u32,
+    ) -> Result<Vec<u32>, DecodeError> {
+        let control = data
+            .get(..control_len(count))
+            .ok_or(DecodeError::Truncated)?;
+        let data = &data[control.len()..];
+        decode_vec(count, |values| {
+            decode_scalar(control, data, 0, values, 0, mode, prev)
+        })
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(encode(&[0]), [0, 0]);
+        assert_eq!(encode(&[u32::MAX]), [3, 0xff, 0xff, 0xff, 0xff]);
+        // the control bytes of all values come before the data
+        assert_eq!(encode(&[1, 2, 3, 4, 5]), [0, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(encode_zigzag(&[-1, 1]), [0, 1, 2]);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        // control bytes of 4 byte values with fewer data bytes
+        assert_eq!(decode(&[0xff; 40], 10), Err(DecodeError::Truncated));
+    }
+
+    #[test]
+    fn zigzag_chunks() {
+        // more values than one chunk, with chunks ending inside the data
+        let signed: Vec<i32> = (0..1001)
+            .map(|i| ((i * 7919) >> (i % 24)) * if i % 2 == 0 { 1 } else { -1 })
+            .collect();
+        let unsigned: Vec<u32> = signed.iter().map(|&value| value as u32).collect();
+        let encoded = encode_zigzag(&signed);
+        assert_eq!(encoded, encode_scalar_only(&unsigned, Mode::ZigZag, 0));
+        assert_eq!(decode_zigzag(&encoded, signed.len()), Ok(signed.clone()));
+        assert_eq!(
+            decode_zigzag(&encoded[..encoded.len() - 1], signed.len()),
+            Err(DecodeError::Truncated)
+        );
+    }
+
+    quickcheck! {
+        fn round_trip(values: Vec<u32>, shifts: Vec<u8>, prev: u32) -> bool {
+            let values = shifted(&values, &shifts);
+            let signed: Vec<i32> = values.iter().map(|&value| value as i32).collect();
+            let n = values.len();
+
+            let plain = encode(&values);
+            let delta = encode_delta(&values, prev);
+            let zigzag = encode_zigzag(&signed);
+            plain == encode_scalar_only(&values, Mode::Plain, 0)
+                && delta == encode_scalar_only(&values, Mode::Delta, prev)
+                && zigzag == encode_scalar_only(&values, Mode::ZigZag, 0)
+                && decode(&plain, n) == Ok(values.clone())
+                && decode_delta(&delta, n, prev) == Ok(values.clone())
+                && decode_zigzag(&zigzag, n) == Ok(signed)
+        }
+    }
+
+    quickcheck! {
+        fn differential_decode(data: Vec<u8>, count: u8, prev: u32) -> bool {
+            let n = count as usize;
+            decode(&data, n) == decode_scalar_only(&data, n, Mode::Plain, 0)
+                && decode_delta(&data, n, prev) == decode_scalar_only(&data, n, Mode::Delta, prev)
+                && decode_zigzag(&data, n).map(|v| v.iter().map(|&x| x as u32).collect())
+                    == decode_scalar_only(&data, n, Mode::ZigZag, 0)
+        }
+    }
+}
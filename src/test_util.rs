@@ -0,0 +1,32 @@
+//! Helpers shared by the tests of several modules.
+use core::ops::Shr;
+use std::vec::Vec;
+
+/// Shifts every value right by the next of `shifts`, repeated as needed, so that
+/// random values cover every encoded length instead of mostly the longest.
+///
+/// The values are kept unshifted when `shifts` is empty.
+pub(crate) fn shifted<T: Copy + Shr<u32, Output = T>>(values: &[T], shifts: &[u8]) -> Vec<T> {
+    let bits = 8 * core::mem::size_of::<T>() as u32;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| {
+            if shifts.is_empty() {
+                value
+            } else {
+                value >> (shifts[i % shifts.len()] as u32 % bits)
+            }
+        })
+        .collect()
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifted_without_shifts() {
+        assert_eq!(shifted(&[u32::MAX, 5], &[]), [u32::MAX, 5]);
+        assert_eq!(shifted(&[u32::MAX, 5, 8], &[31, 2]), [1, 1, 0]);
+    }
+}
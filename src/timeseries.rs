@@ -0,0 +1,212 @@
+//! Delta-of-delta encoding of `i64` timestamps.
+//!
+//! The encoding is a sequence of ZigZag encoded varints: the first value, the
+//! difference between the first two values, and then for every following value the
+//! change of that difference, the delta-of-delta. For regularly spaced timestamps
+//! the delta-of-delta is 0 and every value after the second takes a single byte.
+//! Differences are computed with wrapping arithmetic, so any sequence round trips.
+//!
+//! ```
+//!    use varint::timeseries;
+//!
+//!    let timestamps = [1_600_000_000, 1_600_000_010, 1_600_000_020, 1_600_000_031];
+//!    let encoded = timeseries::encode(&timestamps);
+//!    assert_eq!(encoded, [128, 192, 240, 245, 11, 20, 0, 2]);
+//!    assert_eq!(timeseries::decode(&encoded), Ok(timestamps.to_vec()));
+//! ```
+use alloc::vec::Vec;
+use core::iter::FusedIterator;
+
+use crate::{DecodeError, VarIntEncode, VarIntIter};
+
+/// Streaming encoder appending one timestamp at a time.
+///
+/// ```
+///    use varint::timeseries::Encoder;
+///
+///    let mut encoder = Encoder::new();
+///    for t in (0..100).map(|i| 1_600_000_000 + 10 * i) {
+///        encoder.push(t);
+///    }
+///    assert_eq!(encoder.len(), 100);
+///    assert_eq!(encoder.finish().len(), 5 + 1 + 98);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    output: Vec<u8>,
+    len: usize,
+    prev: i64,
+    prev_delta: i64,
+}
+
+impl Encoder {
+    /// Creates an encoder without any timestamps.
+    pub fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    /// Appends `value` to the encoding.
+    pub fn push(&mut self, value: i64) {
+        let delta = value.wrapping_sub(self.prev);
+        let encoded = match self.len {
+            0 => value,
+            1 => delta,
+            _ => delta.wrapping_sub(self.prev_delta),
+        };
+        let (array, len) = encoded.encode_to_array();
+        self.output.extend_from_slice(&array[..len]);
+        self.prev_delta = if self.len == 0 { 0 } else { delta };
+        self.prev = value;
+        self.len += 1;
+    }
+
+    /// Returns the number of timestamps pushed.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no timestamps were pushed.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the encoding of the timestamps pushed so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Returns the encoding of all timestamps pushed.
+    pub fn finish(self) -> Vec<u8> {
+        self.output
+    }
+}
+
+/// Iterator decoding the timestamps of an encoding, one `Result` per timestamp.
+///
+/// Like `VarIntIter`, the iterator ends after an error.
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+    varints: VarIntIter<'a, i64>,
+    len: usize,
+    prev: i64,
+    prev_delta: i64,
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a decoder of the timestamps encoded in `data`.
+    pub fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder {
+            varints: VarIntIter::new(data),
+            len: 0,
+            prev: 0,
+            prev_delta: 0,
+        }
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = Result<i64, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<i64, DecodeError>> {
+        let encoded = match self.varints.next()? {
+            Ok(encoded) => encoded,
+            Err(err) => return Some(Err(err)),
+        };
+        match self.len {
+            0 => self.prev = encoded,
+            1 => {
+                self.prev_delta = encoded;
+                self.prev = self.prev.wrapping_add(encoded);
+            }
+            _ => {
+                self.prev_delta = self.prev_delta.wrapping_add(encoded);
+                self.prev = self.prev.wrapping_add(self.prev_delta);
+            }
+        }
+        self.len += 1;
+        Some(Ok(self.prev))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.varints.size_hint()
+    }
+}
+
+impl FusedIterator for Decoder<'_> {}
+
+/// Encodes `values`.
+pub fn encode(values: &[i64]) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    for &value in values {
+        encoder.push(value);
+    }
+    encoder.finish()
+}
+
+/// Decodes all timestamps of an encoding.
+pub fn decode(data: &[u8]) -> Result<Vec<i64>, DecodeError> {
+    Decoder::new(data).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn small() {
+        assert_eq!(encode(&[]), []);
+        assert_eq!(encode(&[-1]), [1]);
+        assert_eq!(encode(&[5, 3]), [10, 3]);
+        assert_eq!(encode(&[i64::MIN, i64::MAX, i64::MIN]), {
+            let mut expected = i64::MIN.to_varint();
+            expected.extend((-1i64).to_varint());
+            expected.extend(2i64.to_varint());
+            expected
+        });
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(decode(&[10, 3, 0x80]), Err(DecodeError::Truncated));
+        let mut decoder = Decoder::new(&[10, 0x80]);
+        assert_eq!(decoder.next(), Some(Ok(5)));
+        assert_eq!(decoder.next(), Some(Err(DecodeError::Truncated)));
+        assert_eq!(decoder.next(), None);
+        assert_eq!(decoder.size_hint(), (0, Some(0)));
+
+        // the size hint does not promise more timestamps than an error leaves
+        let decoder = Decoder::new(&[0x80; 100]);
+        assert_eq!(decoder.size_hint(), (1, Some(100)));
+        assert_eq!(decoder.count(), 1);
+    }
+
+    quickcheck! {
+        fn timeseries_i64(values: Vec<i64>) -> bool {
+            let encoded = encode(&values);
+            let mut encoder = Encoder::new();
+            let prefixes_match = values.iter().enumerate().all(|(i, &value)| {
+                encoder.push(value);
+                decode(encoder.as_bytes()).as_deref() == Ok(&values[..=i])
+            });
+            prefixes_match && encoder.finish() == encoded && decode(&encoded) == Ok(values)
+        }
+    }
+
+    quickcheck! {
+        fn timeseries_regular(start: i64, step: i16, jitter: Vec<i8>) -> bool {
+            let values: Vec<i64> = jitter
+                .iter()
+                .enumerate()
+                .map(|(i, &j)| {
+                    let offset = (i as i64).wrapping_mul(step as i64) + (j % 2) as i64;
+                    start.wrapping_add(offset)
+                })
+                .collect();
+            let encoded = encode(&values);
+            // every delta-of-delta is between -4 and 4
+            encoded.len() <= 2 * 10 + values.len()
+                && decode(&encoded) == Ok(values)
+        }
+    }
+}
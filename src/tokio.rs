@@ -0,0 +1,57 @@
+//! Extension traits to read and write varints from tokio `AsyncRead`, `AsyncBufRead`
+//! and `AsyncWrite`.
+//!
+//! ```
+//!    use varint::tokio::{AsyncVarIntBufReadExt, AsyncVarIntReadExt, AsyncVarIntWriteExt};
+//!
+//!    # futures::executor::block_on(async {
+//!    let mut buf = Vec::new();
+//!    buf.write_varint(300u32).await.unwrap();
+//!    buf.write_varint(-300i32).await.unwrap();
+//!
+//!    let mut reader = &buf[..];
+//!    assert_eq!(300u32, reader.read_varint().await.unwrap());
+//!    assert_eq!(-300i32, reader.read_buffered_varint().await.unwrap());
+//!    # });
+//! ```
+use ::tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+crate::async_io::async_varint_ext!("tokio");
+
+/// Reads at most one byte into `byte` and returns the number of bytes read.
+fn poll_read_byte<R: AsyncRead + Unpin + ?Sized>(
+    reader: &mut R,
+    cx: &mut Context<'_>,
+    byte: &mut [u8; 1],
+) -> Poll<io::Result<usize>> {
+    let mut buf = ReadBuf::new(byte);
+    ready!(Pin::new(reader).poll_read(cx, &mut buf))?;
+    Poll::Ready(Ok(buf.filled().len()))
+}
+
+#[cfg(test)]
+impl AsyncRead for crate::async_io::Trickle<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let chunk = ready!(this.poll_chunk(cx));
+        let n = chunk.len().min(buf.remaining());
+        buf.put_slice(&chunk[..n]);
+        this.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+impl AsyncBufRead for crate::async_io::Trickle<'_> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        self.get_mut().poll_chunk(cx).map(Ok)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().consume(amt);
+    }
+}
@@ -0,0 +1,184 @@
+//! Codec for frames prefixed with a varint length, for use with `tokio_util::codec`.
+//!
+//! This is the framing of protobuf's `writeDelimitedTo`/`parseDelimitedFrom`.
+//!
+//! ```
+//!    use bytes::{Bytes, BytesMut};
+//!    use tokio_util::codec::{Decoder, Encoder};
+//!    use varint::tokio_util::VarIntLengthCodec;
+//!
+//!    let mut codec = VarIntLengthCodec::new();
+//!    let mut buf = BytesMut::new();
+//!    codec.encode(Bytes::from_static(b"hello"), &mut buf).unwrap();
+//!    assert_eq!(&buf[..], b"\x05hello");
+//!    assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], b"hello");
+//! ```
+use std::io;
+
+use ::bytes::{Buf, Bytes, BytesMut};
+use ::tokio_util::codec::{Decoder, Encoder};
+
+use crate::bytes::VarIntBufMut;
+use crate::{DecodeError, VarIntDecode};
+
+/// Codec for frames prefixed with their length as a varint.
+///
+/// Frames longer than the maximum frame length (8 MiB by default) are rejected
+/// with an `InvalidData` error when decoding and an `InvalidInput` error when
+/// encoding, before any buffer space is reserved for them.
+#[derive(Debug, Clone)]
+pub struct VarIntLengthCodec {
+    max_frame_length: usize,
+    state: DecodeState,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DecodeState {
+    Head,
+    Data(usize),
+}
+
+impl VarIntLengthCodec {
+    /// Creates a codec with the default maximum frame length of 8 MiB.
+    pub fn new() -> VarIntLengthCodec {
+        VarIntLengthCodec::with_max_frame_length(8 * 1024 * 1024)
+    }
+
+    /// Creates a codec that accepts frames of at most `max_frame_length` bytes.
+    pub fn with_max_frame_length(max_frame_length: usize) -> VarIntLengthCodec {
+        VarIntLengthCodec {
+            max_frame_length,
+            state: DecodeState::Head,
+        }
+    }
+
+    /// Returns the maximum frame length.
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    /// Sets the maximum frame length.
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.max_frame_length = max_frame_length;
+    }
+
+    fn decode_head(&mut self, src: &mut BytesMut) -> io::Result<Option<usize>> {
+        let (len, head_len) = match u64::try_from_varint_with_len(src) {
+            Ok(decoded) => decoded,
+            Err(DecodeError::Empty) | Err(DecodeError::Truncated) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if len > self.max_frame_length as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frame length exceeds maximum",
+            ));
+        }
+        let len = len as usize;
+        src.advance(head_len);
+        src.reserve(len.saturating_sub(src.len()));
+        Ok(Some(len))
+    }
+}
+
+impl Default for VarIntLengthCodec {
+    fn default() -> VarIntLengthCodec {
+        VarIntLengthCodec::new()
+    }
+}
+
+impl Decoder for VarIntLengthCodec {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        let len = match self.state {
+            DecodeState::Head => match self.decode_head(src)? {
+                Some(len) => {
+                    self.state = DecodeState::Data(len);
+                    len
+                }
+                None => return Ok(None),
+            },
+            DecodeState::Data(len) => len,
+        };
+        if src.len() < len {
+            return Ok(None);
+        }
+        self.state = DecodeState::Head;
+        Ok(Some(src.split_to(len)))
+    }
+}
+
+impl Encoder<Bytes> for VarIntLengthCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, data: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        if data.len() > self.max_frame_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "frame length exceeds maximum",
+            ));
+        }
+        dst.reserve(10 + data.len());
+        dst.put_varint(data.len() as u64);
+        dst.extend_from_slice(&data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn decode_partial() {
+        let mut codec = VarIntLengthCodec::new();
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&[0xac]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(&[0x02, 1, 2, 3]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert!(buf.capacity() >= 300);
+        buf.extend_from_slice(&[0; 297]);
+        buf.extend_from_slice(&[0x01, 42]);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().len(), 300);
+        assert_eq!(&codec.decode(&mut buf).unwrap().unwrap()[..], &[42]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn decode_too_long() {
+        let mut codec = VarIntLengthCodec::with_max_frame_length(299);
+        let mut buf = BytesMut::from(&[0xac, 0x02][..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut buf = BytesMut::from(&[0xff; 20][..]);
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn encode_too_long() {
+        let mut codec = VarIntLengthCodec::with_max_frame_length(2);
+        let mut buf = BytesMut::new();
+        let err = codec
+            .encode(Bytes::from_static(b"abc"), &mut buf)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+    }
+
+    quickcheck! {
+        fn encode_decode(frames: Vec<Vec<u8>>) -> bool {
+            let mut codec = VarIntLengthCodec::new();
+            let mut buf = BytesMut::new();
+            for frame in &frames {
+                codec.encode(Bytes::from(frame.clone()), &mut buf).unwrap();
+            }
+            frames.iter().all(|frame| codec.decode(&mut buf).unwrap().unwrap() == frame[..])
+                && buf.is_empty()
+        }
+    }
+}
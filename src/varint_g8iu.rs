@@ -0,0 +1,201 @@
+//! Varint-G8IU encoding of `u32` slices.
+//!
+//! Varint-G8IU, from "SIMD-Based Decoding of Posting Lists" by Stepanov et al.,
+//! stores values in blocks of 9 bytes: a descriptor byte followed by 8 data bytes.
+//! Every value takes 1 to 4 little endian bytes, at least one byte for 0, and as
+//! many whole values as fit are put in each block; a value never spans two blocks.
+//! Bit `k` of the descriptor, counting from the least significant bit, is 0 when
+//! data byte `k` is the last byte of a value. The data bytes after the last value
+//! of a block are 0, with descriptor bits of 1.
+//!
+//! Like the other formats of the `codec` module, the encoding does not store the
+//! number of values. Blocks are decoded with SSE4.1 when the CPU supports it,
+//! detected like in `decode_packed_u32`.
+//!
+//! ```
+//!    use varint::varint_g8iu;
+//!
+//!    let values = [1, 256, 65536, 16777216];
+//!    let mut buf = [0; varint_g8iu::max_encoded_len(4)];
+//!    let len = varint_g8iu::encode_into(&values, &mut buf);
+//!    assert_eq!(
+//!        buf[..len],
+//!        [0b1101_1010, 1, 0, 1, 0, 0, 1, 0, 0, 0b1111_0111, 0, 0, 0, 1, 0, 0, 0, 0]
+//!    );
+//!
+//!    let mut decoded = [0; 4];
+//!    assert_eq!(varint_g8iu::decode_into(&buf[..len], &mut decoded), Ok(len));
+//!    assert_eq!(decoded, values);
+//! ```
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::DecodeError;
+
+/// Number of bytes in a block, the descriptor and the data bytes.
+const BLOCK_LEN: usize = 9;
+
+/// Returns the maximum number of bytes needed to encode `count` values.
+pub const fn max_encoded_len(count: usize) -> usize {
+    // at least two values fit in a block
+    BLOCK_LEN * count.div_ceil(2)
+}
+
+/// Encodes `values` into the start of `buf` and returns the number of bytes
+/// written.
+///
+/// Panics when the encoded values do not fit in `buf`, like `Codec::encode_into`.
+pub fn encode_into(values: &[u32], buf: &mut [u8]) -> usize {
+    let mut pos = 0;
+    let mut values = values.iter().peekable();
+    while values.peek().is_some() {
+        let block = &mut buf[pos..pos + BLOCK_LEN];
+        let mut descriptor = 0xff;
+        let mut used = 0;
+        while let Some(value) = values.peek() {
+            let len = (4 - value.leading_zeros() as usize / 8).max(1);
+            if used + len > BLOCK_LEN - 1 {
+                break;
+            }
+            block[1 + used..1 + used + len].copy_from_slice(&value.to_le_bytes()[..len]);
+            used += len;
+            descriptor &= !(1 << (used - 1));
+            values.next();
+        }
+        for byte in &mut block[1 + used..] {
+            *byte = 0;
+        }
+        block[0] = descriptor;
+        pos += BLOCK_LEN;
+    }
+    pos
+}
+
+/// Encodes `values`.
+#[cfg(feature = "alloc")]
+pub fn encode(values: &[u32]) -> Vec<u8> {
+    crate::codec::encode_vec(max_encoded_len(values.len()), |buf| {
+        encode_into(values, buf)
+    })
+}
+
+/// Decodes `values.len()` values from `data` and returns the number of bytes read,
+/// up to the end of the block holding the last value.
+///
+/// Fails with `Overflow` when a value takes more than 4 bytes.
+pub fn decode_into(data: &[u8], values: &mut [u32]) -> Result<usize, DecodeError> {
+    let (start, pos) = decode_simd(data, values);
+    decode_scalar(data, pos, values, start)
+}
+
+/// Decodes `values[start..]` from the blocks starting at `pos` and returns the end
+/// of the last block read.
+fn decode_scalar(
+    data: &[u8],
+    mut pos: usize,
+    values: &mut [u32],
+    start: usize,
+) -> Result<usize, DecodeError> {
+    let mut i = start;
+    while i < values.len() {
+        let block = data
+            .get(pos..pos + BLOCK_LEN)
+            .ok_or(DecodeError::Truncated)?;
+        let mut value = 0u32;
+        let mut len = 0;
+        for (k, &byte) in block[1..].iter().enumerate() {
+            if len < 4 {
+                value |= (byte as u32) << (8 * len);
+            }
+            len += 1;
+            if block[0] & (1 << k) == 0 {
+                if len > 4 {
+                    return Err(DecodeError::Overflow { target_bits: 32 });
+                }
+                values[i] = value;
+                i += 1;
+                if i == values.len() {
+                    break;
+                }
+                value = 0;
+                len = 0;
+            }
+        }
+        pos += BLOCK_LEN;
+    }
+    Ok(pos)
+}
+
+/// Decodes `count` values from `data`.
+///
+/// Bytes after the block holding the last value are ignored.
+#[cfg(feature = "alloc")]
+pub fn decode(data: &[u8], count: usize) -> Result<Vec<u32>, DecodeError> {
+    crate::codec::decode_vec(count, |values| decode_into(data, values))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn decode_simd(data: &[u8], values: &mut [u32]) -> (usize, usize) {
+    if crate::simd::features().1 {
+        unsafe { crate::simd::varint_g8iu::decode(data, values) }
+    } else {
+        (0, 0)
+    }
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+fn decode_simd(_: &[u8], _: &mut [u32]) -> (usize, usize) {
+    (0, 0)
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+    use crate::test_util::shifted;
+    use std::vec::Vec;
+
+    #[test]
+    fn layout() {
+        assert_eq!(encode(&[0]), [0xfe, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            encode(&[1; 9]),
+            [0, 1, 1, 1, 1, 1, 1, 1, 1, 0xfe, 1, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(max_encoded_len(3), 18);
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            decode(&[0xfe, 1, 0, 0, 0, 0, 0, 0], 1),
+            Err(DecodeError::Truncated)
+        );
+        // the first value takes 5 bytes
+        assert_eq!(
+            decode(&[0b1110_1111, 1, 1, 1, 1, 1, 1, 1, 1], 1),
+            Err(DecodeError::Overflow { target_bits: 32 })
+        );
+        assert_eq!(decode(&[0xfe, 7, 0, 0, 0, 0, 0, 0, 0, 42], 1), Ok(vec![7]));
+        // padding longer than 4 bytes
+        let data = [0xfe, 7, 0, 0, 0, 0, 0, 0, 0, 0xfe, 8, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(decode(&data, 2), Ok(vec![7, 8]));
+    }
+
+    quickcheck! {
+        fn varint_g8iu_u32(values: Vec<u32>, shifts: Vec<u8>) -> bool {
+            let values = shifted(&values, &shifts);
+            let encoded = encode(&values);
+            encoded.len() <= max_encoded_len(values.len())
+                && decode(&encoded, values.len()) == Ok(values)
+        }
+    }
+
+    quickcheck! {
+        fn differential_decode(data: Vec<u8>, count: u8) -> bool {
+            let scalar = crate::codec::decode_vec(count as usize, |values| {
+                decode_scalar(&data, 0, values, 0)
+            });
+            decode(&data, count as usize) == scalar
+        }
+    }
+}